@@ -1,41 +1,229 @@
-use std::env;
-use std::sync::LazyLock;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
+use std::{fs, io};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::metrics::MAX_WORKERS;
 
 // main
 pub const CAPACITY_MSGS: usize = 1 << 16;
 pub const CAPACITY_REQS: usize = 1 << 12;
 pub const CAPACITY_STATUS: usize = 1 << 10;
-pub const WORKERS_CRAWLERS: usize = 4;
-pub const WORKERS_PUBLISHERS: usize = 4;
+// how long a `relay.db` write waits on another connection's lock before failing
+pub const RELAY_DB_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
 
 // server
-pub const PORT: u16 = if cfg!(feature = "labeler") { 9001 } else { 9000 };
-pub const HOSTS_RELAY: &str = "relay1.us-west.bsky.network";
 pub const HOSTS_INTERVAL: Duration = Duration::from_secs(60 * 60);
 pub const HOSTS_MIN_ACCOUNTS: u64 = 0;
+pub const LIST_HOSTS_LIMIT: u16 = 200;
+pub const LIST_HOSTS_LIMIT_MAX: u16 = 1000;
+pub const HOSTS_VERIFY_MAX: usize = 16;
+pub const HOSTS_VERIFY_TIMEOUT: Duration = Duration::from_secs(10);
 
-// resolver
-pub static DO_PLC_EXPORT: LazyLock<bool> = LazyLock::new(|| {
-    !cfg!(feature = "labeler") && env::args().filter(|arg| arg == "--no-plc-export").count() == 0
-});
-pub const PLC_EXPORT_INTERVAL: Duration = Duration::from_secs(60);
-pub const CAPACITY_CACHE: usize = 1 << 18;
+// crawler
+pub const HOSTS_IDLE: Duration = Duration::from_secs(30 * 60);
+pub const HOSTS_IDLE_CHECK: Duration = Duration::from_secs(10);
+pub const HOSTS_OFFLINE_FAILURES: u32 = 10;
+pub const HOSTS_PING_INTERVAL: Duration = Duration::from_secs(30);
+pub const HOSTS_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+pub const HOSTS_REBALANCE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+// fraction of the busiest worker's bytes per second the idlest one must trail by to move a host
+pub const HOSTS_REBALANCE_SPREAD: f64 = 0.5;
 
 // validator
 pub const HOSTS_WRITE_INTERVAL: Duration = Duration::from_secs(10);
+// how long a desynchronized repo waits before its pds is asked again
+pub const REPOS_RESYNC_RETRY: Duration = Duration::from_secs(5 * 60);
+// pause between two `getRepo` calls to the same pds
+pub const REPOS_RESYNC_INTERVAL: Duration = Duration::from_secs(1);
+pub const REPOS_RESYNC_MAX: usize = 16;
+pub const REPOS_RESYNC_QUEUE: usize = 1024;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("toml error: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("{0} must be between 1 and {max}", max = MAX_WORKERS)]
+    Workers(&'static str),
+    #[error("{0} must be at least 1")]
+    Zero(&'static str),
+}
+
+/// Runtime configuration, read from a TOML file and overridden by command-line flags.
+///
+/// Every field is optional in the file and falls back to the defaults below.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // main
+    pub workers_crawlers: usize,
+    pub workers_publishers: usize,
+
+    // server
+    /// port of the default listener, used when `listeners` is empty
+    pub port: u16,
+    pub listeners: Vec<ListenerConfig>,
+    /// relay queried every hour for hosts to crawl
+    pub hosts_relay: String,
+    /// hosts crawled even if they aren't public DNS names, eg local stand-ins in tests
+    pub hosts_allowlist: Vec<String>,
+    /// crawl every host over plain `ws://`, accepting ports and private addresses, eg a local PDS
+    /// on `localhost:2583`
+    pub dev_mode: bool,
+
+    // crawler
+    /// events read per second from a host, plus one per thousand accounts on it
+    pub host_events_per_second: u32,
+    /// events read per hour from a host, plus one per account on it
+    pub host_events_per_hour: u32,
+    /// accounts a host may serve before events for new ones are rejected
+    pub host_max_accounts: u64,
+    /// move hosts off the busiest crawler worker, reconnecting them at the validator's cursor
+    pub crawler_rebalance: bool,
+
+    // resolver
+    /// mirror plc.directory into `plc_directory_db` instead of resolving dids one at a time
+    pub plc_export: bool,
+    pub plc_export_interval_secs: u64,
+    pub capacity_cache: usize,
+
+    // validator
+    /// reject commits whose ops can't be inverted back to the previous repo state
+    pub strict_mst: bool,
+    /// repo states kept in memory, the rest are read from `relay_db`
+    pub capacity_repos: usize,
+    /// fetch and verify the whole repo of accounts first seen mid-stream
+    pub resync_new_repos: bool,
+    /// how far ahead of our clock a rev may be before it's rejected, later event times are capped
+    pub max_clock_skew_secs: u64,
+
+    // paths
+    pub relay_db: PathBuf,
+    pub plc_directory_db: PathBuf,
+    pub db_path: PathBuf,
+
+    // firehose
+    pub disk_size: u64,
+    /// how long events are kept in the firehose, `0` keeps them until `disk_size` is reached
+    pub ttl_seconds: u64,
+
+    // fjall db
+    pub cache_size: u64,
+    pub write_buffer_size: u64,
+    /// `0` disables the background fsync
+    pub fsync_ms: u16,
+    pub memtable_size: u32,
+    pub block_size: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            workers_crawlers: 4,
+            workers_publishers: 4,
+            port: if cfg!(feature = "labeler") { 9001 } else { 9000 },
+            listeners: Vec::new(),
+            hosts_relay: "relay1.us-west.bsky.network".to_owned(),
+            hosts_allowlist: Vec::new(),
+            dev_mode: false,
+            host_events_per_second: 50,
+            host_events_per_hour: 2500,
+            host_max_accounts: 1_000_000,
+            crawler_rebalance: false,
+            plc_export: !cfg!(feature = "labeler"),
+            plc_export_interval_secs: 60,
+            capacity_cache: 1 << 18,
+            strict_mst: false,
+            capacity_repos: 1 << 20,
+            resync_new_repos: false,
+            max_clock_skew_secs: 5 * 60,
+            relay_db: PathBuf::from("relay.db"),
+            plc_directory_db: PathBuf::from("plc_directory.db"),
+            db_path: PathBuf::from("db"),
+            disk_size: 32 * 1024 * 1024 * 1024, // 32 GiB
+            ttl_seconds: if cfg!(feature = "labeler") {
+                0
+            } else {
+                24 * 60 * 60 // 24 hours
+            },
+            cache_size: 1024 * 1024 * 1024,       // 1 GiB
+            write_buffer_size: 512 * 1024 * 1024, // 512 MiB
+            fsync_ms: 1000,                       // 1 second
+            memtable_size: 64 * 1024 * 1024,      // 64 MiB
+            block_size: 64 * 1024,                // 64 KiB
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config file at `path`, or returns the defaults when there is none.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let contents = fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// Rejects settings the relay can't run with, checked once the command-line overrides are
+    /// applied.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for (key, workers) in [
+            ("workers_crawlers", self.workers_crawlers),
+            ("workers_publishers", self.workers_publishers),
+        ] {
+            if !(1..=MAX_WORKERS).contains(&workers) {
+                return Err(ConfigError::Workers(key));
+            }
+        }
+        for (key, capacity) in
+            [("capacity_cache", self.capacity_cache), ("capacity_repos", self.capacity_repos)]
+        {
+            if capacity == 0 {
+                return Err(ConfigError::Zero(key));
+            }
+        }
+        Ok(())
+    }
+
+    /// Configured listeners, or a single one on all IPv4 interfaces serving the public routes.
+    ///
+    /// The admin routes and `/metrics` are only served on listeners explicitly marked `admin`.
+    #[must_use]
+    pub fn listeners(&self, tls: bool) -> Vec<ListenerConfig> {
+        if self.listeners.is_empty() {
+            let address = SocketAddr::from((Ipv4Addr::UNSPECIFIED, self.port));
+            vec![ListenerConfig { address, tls, admin: false }]
+        } else {
+            self.listeners.clone()
+        }
+    }
+
+    #[must_use]
+    pub const fn plc_export_interval(&self) -> Duration {
+        Duration::from_secs(self.plc_export_interval_secs)
+    }
+
+    #[must_use]
+    pub const fn max_clock_skew(&self) -> Duration {
+        Duration::from_secs(self.max_clock_skew_secs)
+    }
+}
 
-// firehose
-pub const DISK_SIZE: u64 = 32 * 1024 * 1024 * 1024; // 32 GiB
-pub const TTL_SECONDS: Option<u64> = if cfg!(feature = "labeler") {
-    None
-} else {
-    Some(24 * 60 * 60) // 24 hours
-};
-
-// fjall db
-pub const CACHE_SIZE: u64 = 1024 * 1024 * 1024; // 1 GiB
-pub const WRITE_BUFFER_SIZE: u64 = 512 * 1024 * 1024; // 512 MiB
-pub const FSYNC_MS: Option<u16> = Some(1000); // 1 second
-pub const MEMTABLE_SIZE: u32 = 64 * 1024 * 1024; // 64 MiB
-pub const BLOCK_SIZE: u32 = 64 * 1024; // 64 KiB
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListenerConfig {
+    /// eg, `127.0.0.1:9000` or `[::]:443`
+    pub address: SocketAddr,
+    /// serve TLS using the `--certs`/`--private-key` pair
+    #[serde(default)]
+    pub tls: bool,
+    /// also serve the `/admin` routes
+    #[serde(default)]
+    pub admin: bool,
+}
@@ -1,92 +1,134 @@
+use std::collections::VecDeque;
 use std::io;
-use std::net::{TcpStream, ToSocketAddrs};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::os::fd::AsRawFd;
+use std::time::{Duration, Instant};
 
 use http::Uri;
-use http::request::Parts;
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token};
 use socket2::{Domain, Protocol, Socket, Type};
 use tungstenite::client::{IntoClientRequest, uri_mode};
 use tungstenite::client_tls_with_config;
 use tungstenite::error::{Error, Result, UrlError};
-use tungstenite::handshake::client::Request;
-use tungstenite::protocol::WebSocketConfig;
-use tungstenite::stream::{Mode, NoDelay};
+use tungstenite::stream::Mode;
 
 use crate::crawler::types::{DecomposeError, HandshakeResult};
 
-/// Connect to the given WebSocket in blocking mode.
-///
-/// The URL may be either ws:// or wss://.
-/// To support wss:// URLs, feature `native-tls` or `rustls-tls` must be turned on.
+/// Delay before racing the next address while earlier attempts are still pending (RFC 8305 §5).
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// A non-blocking WebSocket connection being established.
 ///
-/// This function "just works" for those who wants a simple blocking solution
-/// similar to `std::net::TcpStream`. If you want a non-blocking or other
-/// custom stream, call `client` instead.
+/// The resolved addresses are interleaved by family and raced "Happy Eyeballs" style: a new
+/// attempt starts every `CONNECTION_ATTEMPT_DELAY`, or as soon as the previous one fails, and the
+/// first socket to finish connecting wins. Every socket is registered with the worker's `Poll`
+/// for writability, which is when `poll` should be called again.
 ///
-/// This function uses `native_tls` or `rustls` to do TLS depending on the feature flags enabled. If
-/// you want to use other TLS libraries, use `client` instead. There is no need to enable any of
-/// the `*-tls` features if you don't call `connect` since it's the only function that uses them.
-pub fn connect<Req: IntoClientRequest>(request: Req) -> HandshakeResult {
-    connect_with_config(request, None, 3)
+/// Redirects are refused rather than followed, as their target was never checked against the
+/// hostname and public address rules the host went through before being crawled.
+pub struct Connecting {
+    uri: Uri,
+    candidates: VecDeque<SocketAddr>,
+    attempts: Vec<Socket>,
+    next_attempt: Instant,
 }
 
-// Ref: https://github.com/snapview/tungstenite-rs/blob/master/src/client.rs
-#[expect(
-    clippy::expect_used,
-    clippy::ignored_unit_patterns,
-    clippy::redundant_clone,
-    clippy::redundant_else
-)]
-pub fn connect_with_config<Req: IntoClientRequest>(
-    request: Req, config: Option<WebSocketConfig>, max_redirects: u8,
-) -> HandshakeResult {
-    fn try_client_handshake(request: Request, config: Option<WebSocketConfig>) -> HandshakeResult {
-        let uri = request.uri();
-        let mode = uri_mode(uri)?;
-
-        let host = request.uri().host().ok_or(Error::Url(UrlError::NoHostName))?;
-        let host = if host.starts_with('[') { &host[1..host.len() - 1] } else { host };
+impl Connecting {
+    /// Prepares the connection attempts to the already resolved addresses of the request host.
+    ///
+    /// The URL may be either ws:// or wss://.
+    pub fn new<Req: IntoClientRequest>(request: Req, addrs: &[IpAddr]) -> Result<Self> {
+        let request = request.into_client_request()?;
+        let uri = request.uri().clone();
+        let mode = uri_mode(&uri)?;
+
         let port = uri.port_u16().unwrap_or(match mode {
             Mode::Plain => 80,
             Mode::Tls => 443,
         });
-        let mut stream = connect_to_some((host, port), request.uri())?;
-        NoDelay::set_nodelay(&mut stream, true)?;
+        let candidates = interleave(addrs.iter().map(|addr| SocketAddr::new(*addr, port)));
+        if candidates.is_empty() {
+            return Err(Error::Url(UrlError::UnableToConnect(uri.to_string())));
+        }
 
-        client_tls_with_config(request, stream, config, None).decompose()
+        Ok(Self { uri, candidates, attempts: Vec::new(), next_attempt: Instant::now() })
     }
 
-    fn create_request(parts: &Parts, uri: &Uri) -> Request {
-        let mut builder =
-            Request::builder().uri(uri.clone()).method(parts.method.clone()).version(parts.version);
-        *builder.headers_mut().expect("Failed to create `Request`") = parts.headers.clone();
-        builder.body(()).expect("Failed to create `Request`")
+    /// When the next attempt is due if no socket becomes writable before, if any are left.
+    #[inline]
+    pub fn deadline(&self) -> Option<Instant> {
+        (!self.candidates.is_empty()).then_some(self.next_attempt)
     }
 
-    let (parts, _) = request.into_client_request()?.into_parts();
-    let mut uri = parts.uri.clone();
-
-    for attempt in 0..=max_redirects {
-        let request = create_request(&parts, &uri);
-
-        match try_client_handshake(request, config) {
-            Err(Error::Http(res)) if res.status().is_redirection() && attempt < max_redirects => {
-                if let Some(location) = res.headers().get("Location") {
-                    uri = location.to_str()?.parse::<Uri>()?;
-                    // debug!("Redirecting to {uri:?}");
-                    continue;
-                } else {
-                    // warn!("No `Location` found in redirect");
-                    return Err(Error::Http(res));
+    /// Checks the in-flight attempts and starts new ones when due.
+    ///
+    /// Returns `None` while still connecting, or the result of the WebSocket handshake on the
+    /// first connected socket. The remaining attempts are closed when `self` is dropped.
+    pub fn poll(&mut self, registry: &Registry, token: Token) -> Option<HandshakeResult> {
+        let mut idx = 0;
+        while idx < self.attempts.len() {
+            match connected(&self.attempts[idx]) {
+                Ok(true) => {
+                    let socket = self.attempts.swap_remove(idx);
+                    return Some(self.handshake(registry, socket));
+                }
+                Ok(false) => idx += 1,
+                Err(_) => {
+                    self.attempts.swap_remove(idx);
+                    self.next_attempt = Instant::now();
                 }
             }
-            other => return other,
         }
+
+        while self.attempts.is_empty() || self.next_attempt <= Instant::now() {
+            let Some(addr) = self.candidates.pop_front() else {
+                break;
+            };
+            // debug!("Trying to contact {uri} at {addr}...");
+            if let Ok(socket) = start(addr, registry, token) {
+                self.attempts.push(socket);
+                self.next_attempt = Instant::now() + CONNECTION_ATTEMPT_DELAY;
+            }
+        }
+
+        if self.attempts.is_empty() {
+            return Some(Err(Error::Url(UrlError::UnableToConnect(self.uri.to_string()))));
+        }
+        None
+    }
+
+    fn handshake(&self, registry: &Registry, socket: Socket) -> HandshakeResult {
+        // the worker registers the stream again once the handshake is done
+        registry.deregister(&mut SourceFd(&socket.as_raw_fd()))?;
+        let stream = TcpStream::from(socket);
+        client_tls_with_config(self.uri.clone(), stream, None, None).decompose()
     }
+}
 
-    unreachable!("Bug in a redirect handling logic")
+/// Alternates between address families, starting with the one the resolver ranked first.
+fn interleave(addrs: impl Iterator<Item = SocketAddr>) -> VecDeque<SocketAddr> {
+    let mut addrs = addrs.peekable();
+    let prefer_v6 = addrs.peek().is_none_or(SocketAddr::is_ipv6);
+    let (v6, v4): (Vec<_>, Vec<_>) = addrs.partition(SocketAddr::is_ipv6);
+    let (first, second) = if prefer_v6 { (v6, v4) } else { (v4, v6) };
+
+    let mut out = VecDeque::with_capacity(first.len() + second.len());
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => break,
+            (a, b) => {
+                out.extend(a);
+                out.extend(b);
+            }
+        }
+    }
+    out
 }
 
-fn connect_to_some(addrs: impl ToSocketAddrs, uri: &Uri) -> Result<TcpStream> {
+fn start(addr: SocketAddr, registry: &Registry, token: Token) -> io::Result<Socket> {
     fn is_blocking_error(error: &io::Error) -> bool {
         matches!(
             error.kind(),
@@ -94,16 +136,59 @@ fn connect_to_some(addrs: impl ToSocketAddrs, uri: &Uri) -> Result<TcpStream> {
         ) || matches!(error.raw_os_error(), Some(libc::EINPROGRESS))
     }
 
-    for addr in addrs.to_socket_addrs()? {
-        // debug!("Trying to contact {uri} at {addr}...");
-        let socket = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP))?;
-        socket.set_nonblocking(true)?;
-        match socket.connect(&addr.into()) {
-            Ok(()) => {}
-            Err(e) if is_blocking_error(&e) => {}
-            Err(_) => continue,
-        }
-        return Ok(socket.into());
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_nonblocking(true)?;
+    socket.set_nodelay(true)?;
+    match socket.connect(&addr.into()) {
+        Ok(()) => {}
+        Err(e) if is_blocking_error(&e) => {}
+        Err(e) => return Err(e),
+    }
+    registry.register(&mut SourceFd(&socket.as_raw_fd()), token, Interest::WRITABLE)?;
+    Ok(socket)
+}
+
+// false: still connecting
+// true: connected
+fn connected(socket: &Socket) -> io::Result<bool> {
+    if let Some(err) = socket.take_error()? {
+        return Err(err);
+    }
+    match socket.peer_addr() {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotConnected => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use crate::crawler::client::interleave;
+
+    fn addrs(addrs: &[&str]) -> Vec<SocketAddr> {
+        #[expect(clippy::unwrap_used)]
+        addrs.iter().map(|addr| addr.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn interleave_families() {
+        let v4 = ["192.0.2.1:443", "192.0.2.2:443", "192.0.2.3:443"];
+        let v6 = ["[2001:db8::1]:443", "[2001:db8::2]:443"];
+
+        // the first family stays first, the longer one finishes the list
+        let ranked = addrs(&[v6[0], v6[1], v4[0], v4[1], v4[2]]);
+        let expected = addrs(&[v6[0], v4[0], v6[1], v4[1], v4[2]]);
+        assert_eq!(Vec::from(interleave(ranked.into_iter())), expected);
+
+        let ranked = addrs(&[v4[0], v6[0], v4[1], v4[2], v6[1]]);
+        let expected = addrs(&[v4[0], v6[0], v4[1], v6[1], v4[2]]);
+        assert_eq!(Vec::from(interleave(ranked.into_iter())), expected);
+
+        let ranked = addrs(&v4);
+        assert_eq!(Vec::from(interleave(ranked.clone().into_iter())), ranked);
+
+        assert!(interleave(std::iter::empty()).is_empty());
     }
-    Err(Error::Url(UrlError::UnableToConnect(uri.to_string())))
 }
@@ -1,15 +1,22 @@
 use std::io;
+use std::net::IpAddr;
 use std::os::fd::{AsRawFd, RawFd};
+use std::time::{Duration, Instant};
 
+use bytes::Bytes;
 use thingbuf::mpsc;
 use thiserror::Error;
 use tungstenite::Message;
+use tungstenite::error::UrlError;
 use tungstenite::stream::MaybeTlsStream;
 use url::Url;
 
-use crate::crawler::client;
-use crate::crawler::types::{HandshakeResult, WebSocketClient};
+use crate::config::{HOSTS_PING_INTERVAL, HOSTS_TIMEOUT};
+use crate::crawler::client::Connecting;
+use crate::crawler::limiter::RateLimiter;
+use crate::crawler::types::{Load, RateLimits, WebSocketClient};
 use crate::types::{Cursor, MessageSender};
+use crate::validator::Header;
 
 #[derive(Debug, Error)]
 pub enum ConnectionError {
@@ -19,10 +26,27 @@ pub enum ConnectionError {
     Tungstenite(#[from] tungstenite::Error),
     #[error("thingbuf error: {0}")]
     Thingbuf(#[from] mpsc::errors::Closed),
+    #[error("no frame received for {0:?}")]
+    Timeout(Duration),
+    #[error("closed by host: {0}")]
+    Closed(String),
+    #[error("error frame from upstream")]
+    Upstream,
 }
 
 pub struct Connection {
     pub(crate) hostname: String,
+    // last event, for idle detection
+    pub(crate) last: Instant,
+    // last frame of any kind, or time reads were paused, for dead connection detection
+    last_frame: Instant,
+    last_ping: Instant,
+    pub(crate) idle: bool,
+    pub(crate) throttled: bool,
+    // read since the last load report
+    events: u64,
+    bytes: u64,
+    limiter: RateLimiter,
     client: WebSocketClient,
     message_tx: MessageSender,
 }
@@ -39,11 +63,44 @@ impl AsRawFd for Connection {
 }
 
 impl Connection {
-    pub const fn new(hostname: String, client: WebSocketClient, message_tx: MessageSender) -> Self {
-        Self { hostname, client, message_tx }
+    pub fn new(
+        hostname: String, client: WebSocketClient, message_tx: MessageSender, limits: RateLimits,
+    ) -> Self {
+        let now = Instant::now();
+        Self {
+            hostname,
+            last: now,
+            last_frame: now,
+            last_ping: now,
+            idle: false,
+            throttled: false,
+            events: 0,
+            bytes: 0,
+            limiter: RateLimiter::new(limits),
+            client,
+            message_tx,
+        }
+    }
+
+    /// Whether the host used up its hourly event budget.
+    #[inline]
+    pub fn exhausted(&self) -> bool {
+        self.limiter.exhausted()
+    }
+
+    /// Averages the events and bytes read over `elapsed`, and starts counting again.
+    #[expect(clippy::cast_precision_loss)]
+    pub fn take_load(&mut self, elapsed: Duration) -> Load {
+        let secs = elapsed.as_secs_f64().max(1.0);
+        let load = Load { events: self.events as f64 / secs, bytes: self.bytes as f64 / secs };
+        self.events = 0;
+        self.bytes = 0;
+        load
     }
 
-    pub fn connect(hostname: &str, mut cursor: Option<Cursor>) -> HandshakeResult {
+    pub fn connect(
+        hostname: &str, mut cursor: Option<Cursor>, addrs: &[IpAddr], tls: bool,
+    ) -> Result<Connecting, tungstenite::Error> {
         let path = if cfg!(feature = "labeler") {
             if cursor.is_none() {
                 cursor = Some(0.into());
@@ -52,12 +109,31 @@ impl Connection {
         } else {
             "com.atproto.sync.subscribeRepos"
         };
-        #[expect(clippy::unwrap_used)]
-        let mut url = Url::parse(&format!("wss://{hostname}/xrpc/{path}")).unwrap();
+        let scheme = if tls { "wss" } else { "ws" };
+        let mut url = Url::parse(&format!("{scheme}://{hostname}/xrpc/{path}"))
+            .map_err(|_| tungstenite::Error::Url(UrlError::NoHostName))?;
         if let Some(cursor) = cursor {
             url.query_pairs_mut().append_pair("cursor", &cursor.to_string());
         }
-        client::connect(url)
+        Connecting::new(url, addrs)
+    }
+
+    /// Pings hosts that have been silent for a while, and fails once they stay silent for too long.
+    pub fn keepalive(&mut self) -> Result<(), ConnectionError> {
+        let silent = self.last_frame.elapsed();
+        if silent > HOSTS_TIMEOUT {
+            return Err(ConnectionError::Timeout(silent));
+        }
+        if silent > HOSTS_PING_INTERVAL && self.last_ping.elapsed() > HOSTS_PING_INTERVAL {
+            self.last_ping = Instant::now();
+            match self.client.send(Message::Ping(Bytes::new())) {
+                Ok(()) => {}
+                // queued, flushed along with the next read
+                Err(tungstenite::Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(err) => Err(err)?,
+            }
+        }
+        Ok(())
     }
 
     pub fn close(&mut self) -> Result<(), ConnectionError> {
@@ -69,13 +145,27 @@ impl Connection {
     // false: not polled
     // true: polled
     pub fn poll(&mut self) -> Result<bool, ConnectionError> {
+        let mut polled = true;
+        let mut received = false;
+        // a paused connection can't be told apart from a dead one, so count it as alive
+        let mut alive = false;
         for _ in 0..128 {
             if self.message_tx.remaining() < 16 {
-                return Ok(false);
+                polled = false;
+                alive = true;
+                break;
+            }
+            // over the limit, leave the rest in the socket until the buckets refill
+            if !self.limiter.ready() {
+                alive = true;
+                break;
             }
 
             let msg = match self.client.read() {
-                Ok(msg) => msg,
+                Ok(msg) => {
+                    alive = true;
+                    msg
+                }
                 Err(tungstenite::Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {
                     break;
                 }
@@ -88,8 +178,10 @@ impl Connection {
                     continue;
                 }
                 Message::Close(close) => {
-                    tracing::debug!(host = %self.hostname, ?close, "received close");
-                    continue;
+                    // queues the close reply, sent by the flush
+                    let _err = self.client.flush();
+                    let reason = close.map(|frame| frame.to_string()).unwrap_or_default();
+                    return Err(ConnectionError::Closed(reason));
                 }
                 _ => {
                     tracing::debug!(host = %self.hostname, ?msg, "unknown ws message");
@@ -97,11 +189,33 @@ impl Connection {
                 }
             };
 
+            // empty messages mark the end of a connection for the validator
+            if bytes.is_empty() {
+                continue;
+            }
+
+            // still passed on, the validator reads the error and picks the cursor to reconnect at
+            let error = Header::is_error(&bytes);
+            self.limiter.take();
+            self.events += 1;
+            self.bytes += bytes.len() as u64;
             let mut slot = self.message_tx.send_ref()?;
             slot.data = bytes;
             slot.hostname.clone_from(&self.hostname);
+            drop(slot);
+            received = true;
+            if error {
+                return Err(ConnectionError::Upstream);
+            }
+        }
+        if alive {
+            let now = Instant::now();
+            self.last_frame = now;
+            if received {
+                self.last = now;
+            }
         }
-        Ok(true)
+        Ok(polled)
     }
 }
 
@@ -0,0 +1,132 @@
+use std::io;
+use std::net::IpAddr;
+use std::time::Instant;
+
+use hashbrown::{HashMap, HashSet};
+use hickory_resolver::TokioAsyncResolver;
+use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+use hickory_resolver::error::ResolveError;
+use hickory_resolver::lookup_ip::LookupIp;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+use crate::config::{CAPACITY_STATUS, Config};
+use crate::crawler::host::{is_public, split_port};
+use crate::crawler::types::{
+    LookupReceiver, LookupSender, Resolved, ResolvedReceiver, ResolvedSender,
+};
+
+const CACHE_SIZE: usize = 1 << 12;
+
+#[derive(Debug, Error)]
+pub enum DnsError {
+    #[error("resolve error: {0}")]
+    Resolve(#[from] ResolveError),
+    #[error("no public address")]
+    NotPublic,
+}
+
+/// Resolves upstream hostnames for the crawler workers, so a slow name server only delays the
+/// hosts waiting on it rather than every connection of a worker.
+///
+/// Lookups run concurrently on a single-threaded runtime and successful answers are cached until
+/// their TTL expires. Private and loopback addresses are dropped from the answers, except for
+/// hosts in `hosts_allowlist` or in dev mode. The thread exits once every worker has dropped its `LookupSender`.
+pub struct Dns {
+    lookup_rx: LookupReceiver,
+    resolved_tx: Box<[ResolvedSender]>,
+    cache: HashMap<String, (Vec<IpAddr>, Instant)>,
+    allowlist: HashSet<String>,
+    dev_mode: bool,
+}
+
+impl Dns {
+    pub fn new(config: &Config) -> (Self, LookupSender, Vec<ResolvedReceiver>) {
+        let (lookup_tx, lookup_rx) = mpsc::unbounded_channel();
+        let (resolved_tx, resolved_rx) = (0..config.workers_crawlers)
+            .map(|_| rtrb::RingBuffer::new(CAPACITY_STATUS))
+            .unzip::<_, _, Vec<_>, _>();
+        let this = Self {
+            lookup_rx,
+            resolved_tx: resolved_tx.into_boxed_slice(),
+            cache: HashMap::new(),
+            allowlist: config.hosts_allowlist.iter().cloned().collect(),
+            dev_mode: config.dev_mode,
+        };
+        (this, lookup_tx, resolved_rx)
+    }
+
+    pub fn run(self) -> io::Result<()> {
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        rt.block_on(self.run_async());
+        Ok(())
+    }
+
+    async fn run_async(mut self) {
+        let resolver = TokioAsyncResolver::tokio_from_system_conf().unwrap_or_else(|err| {
+            tracing::warn!(%err, "unable to read system resolver config, using defaults");
+            TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())
+        });
+        let mut lookups = JoinSet::new();
+        loop {
+            tokio::select! {
+                lookup = self.lookup_rx.recv() => {
+                    let Some((worker_id, hostname)) = lookup else {
+                        break;
+                    };
+                    let cached = self
+                        .cache
+                        .get(&hostname)
+                        .filter(|(_, until)| *until > Instant::now())
+                        .map(|(addrs, _)| addrs.clone());
+                    if let Some(addrs) = cached {
+                        self.send(worker_id, Resolved { hostname, addrs: Ok(addrs) });
+                        continue;
+                    }
+                    let resolver = resolver.clone();
+                    lookups.spawn(async move {
+                        let name = split_port(&hostname).map_or(hostname.as_str(), |(name, _)| name);
+                        let res = resolver.lookup_ip(name).await;
+                        (worker_id, hostname, res)
+                    });
+                }
+                Some(res) = lookups.join_next() => {
+                    let Ok((worker_id, hostname, res)) = res else {
+                        continue;
+                    };
+                    self.handle_lookup(worker_id, hostname, res);
+                }
+            }
+        }
+    }
+
+    fn handle_lookup(
+        &mut self, worker_id: usize, hostname: String, res: Result<LookupIp, ResolveError>,
+    ) {
+        let allowed = self.dev_mode || self.allowlist.contains(&hostname);
+        let addrs = res.map_err(DnsError::from).and_then(|lookup| {
+            let addrs =
+                lookup.iter().filter(|addr| allowed || is_public(*addr)).collect::<Vec<_>>();
+            if addrs.is_empty() {
+                return Err(DnsError::NotPublic);
+            }
+            if self.cache.len() >= CACHE_SIZE {
+                let now = Instant::now();
+                self.cache.retain(|_, (_, until)| *until > now);
+            }
+            if self.cache.len() < CACHE_SIZE {
+                self.cache.insert(hostname.clone(), (addrs.clone(), lookup.valid_until()));
+            }
+            Ok(addrs)
+        });
+        self.send(worker_id, Resolved { hostname, addrs });
+    }
+
+    fn send(&mut self, worker_id: usize, resolved: Resolved) {
+        if let Err(err) = self.resolved_tx[worker_id].push(resolved) {
+            let rtrb::PushError::Full(resolved) = err;
+            tracing::warn!(host = %resolved.hostname, "dns results full, dropping lookup");
+        }
+    }
+}
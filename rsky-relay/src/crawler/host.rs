@@ -0,0 +1,238 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use thiserror::Error;
+
+// special-use and internal TLDs that never point at a public PDS
+const RESERVED_TLDS: [&str; 9] =
+    ["arpa", "example", "home", "internal", "invalid", "lan", "local", "localhost", "test"];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum HostnameError {
+    #[error("hostname is empty or longer than 253 characters")]
+    Length,
+    #[error("hostname must have at least two labels")]
+    SingleLabel,
+    #[error("invalid label: {0}")]
+    InvalidLabel(String),
+    #[error("ip addresses are not allowed")]
+    IpLiteral,
+    #[error("reserved top-level domain: {0}")]
+    ReservedTld(String),
+    #[error("invalid port: {0}")]
+    Port(String),
+}
+
+/// Checks that `hostname` is a bare public DNS name, eg `pds.example.com`.
+///
+/// Ports, paths, IP literals, single-label names and special-use TLDs are rejected. Hostnames are
+/// expected in lowercase, the resolved addresses are checked separately with `is_public`.
+pub fn validate_hostname(hostname: &str) -> Result<(), HostnameError> {
+    if hostname.is_empty() || hostname.len() > 253 {
+        return Err(HostnameError::Length);
+    }
+    if hostname.starts_with('[') || hostname.parse::<IpAddr>().is_ok() {
+        return Err(HostnameError::IpLiteral);
+    }
+
+    let mut labels = 0;
+    for label in hostname.split('.') {
+        labels += 1;
+        if label.is_empty()
+            || label.len() > 63
+            || label.starts_with('-')
+            || label.ends_with('-')
+            || !label.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        {
+            return Err(HostnameError::InvalidLabel(label.to_owned()));
+        }
+    }
+    if labels < 2 {
+        return Err(HostnameError::SingleLabel);
+    }
+
+    #[expect(clippy::unwrap_used)]
+    let tld = hostname.rsplit('.').next().unwrap();
+    if tld.bytes().all(|b| b.is_ascii_digit()) {
+        // eg an ip address in a shorthand form like `127.1`
+        return Err(HostnameError::IpLiteral);
+    }
+    if RESERVED_TLDS.contains(&tld) {
+        return Err(HostnameError::ReservedTld(tld.to_owned()));
+    }
+    Ok(())
+}
+
+/// Splits an optional port off `hostname`, eg `localhost:2583` or `[::1]:2583`.
+///
+/// Only upstreams in dev mode carry a port, the brackets around IPv6 literals are removed.
+pub fn split_port(hostname: &str) -> Result<(&str, Option<u16>), HostnameError> {
+    let (host, port) = match hostname.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') || host.ends_with(']') => {
+            let port = port.parse().map_err(|_| HostnameError::Port(port.to_owned()))?;
+            (host, Some(port))
+        }
+        _ => (hostname, None),
+    };
+    let host = host.strip_prefix('[').and_then(|host| host.strip_suffix(']')).unwrap_or(host);
+    if host.is_empty() || host.len() > 253 {
+        return Err(HostnameError::Length);
+    }
+    Ok((host, port))
+}
+
+/// Whether `ip` is a globally routable unicast address.
+pub const fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => is_public_v6(ip),
+    }
+}
+
+const fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(a == 0 // "this" network
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || (a == 100 && b & 0xc0 == 64) // shared address space
+        || (a == 192 && b == 0 && c == 0) // ietf protocol assignments
+        || (a == 198 && b & 0xfe == 18) // benchmarking
+        || a >= 240) // reserved
+}
+
+const fn is_public_v6(ip: Ipv6Addr) -> bool {
+    if let Some(ip) = ip.to_ipv4_mapped() {
+        return is_public_v4(ip);
+    }
+    let [a, b, c, d, e, f, g, h] = ip.segments();
+    if a == 0 && b == 0 && c == 0 && d == 0 && e == 0 && f == 0 {
+        // unspecified, loopback, or the deprecated ipv4-compatible form, never routed
+        return false;
+    }
+    // tunnels end up at the embedded ipv4 address, the teredo client's being inverted
+    if a == 0x2002 {
+        return is_public_v4(embedded_v4(b, c)); // 6to4
+    }
+    if a == 0x2001 && b == 0 {
+        return is_public_v4(embedded_v4(!g, !h)); // teredo
+    }
+    !(ip.is_multicast()
+        || a & 0xfe00 == 0xfc00 // unique local
+        || a & 0xffc0 == 0xfe80 // link local
+        || a & 0xffc0 == 0xfec0 // site local
+        || (a == 0x2001 && b == 0x0db8) // documentation
+        || (a == 0x0100 && b == 0) // discard only
+        || (a == 0x0064 && b == 0xff9b)) // nat64, embeds an ipv4 address
+}
+
+const fn embedded_v4(high: u16, low: u16) -> Ipv4Addr {
+    let [a, b] = high.to_be_bytes();
+    let [c, d] = low.to_be_bytes();
+    Ipv4Addr::new(a, b, c, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use crate::crawler::host::{HostnameError, is_public, validate_hostname};
+
+    #[test]
+    fn hostnames() {
+        let label = |label: &str| Err(HostnameError::InvalidLabel(label.to_owned()));
+        let tld = |tld: &str| Err(HostnameError::ReservedTld(tld.to_owned()));
+        let long = format!("{}.com", "a.".repeat(126));
+        let cases = [
+            ("pds.example.com", Ok(())),
+            ("bsky.social", Ok(())),
+            ("xn--bcher-kva.example", tld("example")),
+            ("", Err(HostnameError::Length)),
+            (long.as_str(), Err(HostnameError::Length)),
+            // ip literals
+            ("127.0.0.1", Err(HostnameError::IpLiteral)),
+            ("::1", Err(HostnameError::IpLiteral)),
+            ("[::1]", Err(HostnameError::IpLiteral)),
+            ("127.1", Err(HostnameError::IpLiteral)),
+            // ports, paths and trailing dots
+            ("pds.example.com:443", label("com:443")),
+            ("pds.example.com/xrpc", label("com/xrpc")),
+            ("pds.example.com.", label("")),
+            ("pds..example.com", label("")),
+            // malformed labels
+            ("-pds.example.com", label("-pds")),
+            ("pds-.example.com", label("pds-")),
+            ("PDS.example.com", label("PDS")),
+            ("pds_1.example.com", label("pds_1")),
+            ("localhost", Err(HostnameError::SingleLabel)),
+            // reserved tlds
+            ("pds.localhost", tld("localhost")),
+            ("pds.local", tld("local")),
+            ("pds.internal", tld("internal")),
+            ("pds.test", tld("test")),
+            ("1.0.0.127.in-addr.arpa", tld("arpa")),
+        ];
+        for (hostname, expected) in cases {
+            assert_eq!(validate_hostname(hostname), expected, "{hostname}");
+        }
+    }
+
+    #[test]
+    fn public_addresses() {
+        let cases = [
+            ("1.1.1.1", true),
+            ("8.8.8.8", true),
+            ("0.0.0.0", false),
+            ("255.255.255.255", false),
+            ("224.0.0.1", false),
+            ("240.0.0.1", false),
+            ("192.0.2.1", false),       // documentation
+            ("198.18.0.1", false),      // benchmarking
+            ("192.0.0.8", false),       // ietf protocol assignments
+            ("10.0.0.1", false),        // rfc1918
+            ("172.16.0.1", false),      // rfc1918
+            ("172.31.255.255", false),  // rfc1918
+            ("192.168.1.1", false),     // rfc1918
+            ("100.64.0.1", false),      // cgnat
+            ("100.127.255.255", false), // cgnat
+            ("100.128.0.1", true),
+            ("127.0.0.1", false),       // loopback
+            ("127.1.2.3", false),       // loopback
+            ("169.254.169.254", false), // link local
+            ("2606:4700:4700::1111", true),
+            ("::", false),
+            ("::1", false),
+            ("fc00::1", false),      // unique local
+            ("fd12:3456::1", false), // unique local
+            ("fe80::1", false),      // link local
+            ("fec0::1", false),      // site local
+            ("ff02::1", false),
+            ("2001:db8::1", false),
+            ("100::1", false),
+            ("64:ff9b::101:101", false),
+            // ipv4-mapped
+            ("::ffff:1.1.1.1", true),
+            ("::ffff:10.0.0.1", false),
+            ("::ffff:127.0.0.1", false),
+            ("::ffff:169.254.169.254", false),
+            // ipv4-compatible
+            ("::1.1.1.1", false),
+            ("::127.0.0.1", false),
+            // 6to4
+            ("2002:101:101::1", true),
+            ("2002:a00:1::1", false),
+            ("2002:7f00:1::1", false),
+            // teredo, with the client address inverted
+            ("2001:0:4136:e378:8000:63bf:fefe:fefe", true),
+            ("2001:0:4136:e378:8000:63bf:f5ff:fffe", false),
+            ("2001:0:4136:e378:8000:63bf:80ff:fffe", false),
+        ];
+        for (ip, expected) in cases {
+            #[expect(clippy::unwrap_used)]
+            let addr = ip.parse::<IpAddr>().unwrap();
+            assert_eq!(is_public(addr), expected, "{ip}");
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::time::Instant;
+
+use crate::crawler::types::RateLimits;
+
+/// Token buckets pacing the events read from a single host.
+///
+/// The per-second bucket only smooths out bursts, an empty per-hour bucket means the host is
+/// sending more than it's allowed to and is reported as throttled.
+pub struct RateLimiter {
+    second: Bucket,
+    hour: Bucket,
+    last: Instant,
+}
+
+struct Bucket {
+    tokens: f64,
+    capacity: f64,
+    rate: f64,
+}
+
+impl Bucket {
+    fn new(capacity: u32, period_secs: u32) -> Self {
+        let capacity = f64::from(capacity.max(1));
+        Self { tokens: capacity, capacity, rate: capacity / f64::from(period_secs) }
+    }
+
+    #[inline]
+    fn refill(&mut self, elapsed: f64) {
+        self.tokens = self.rate.mul_add(elapsed, self.tokens).min(self.capacity);
+    }
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            second: Bucket::new(limits.per_second, 1),
+            hour: Bucket::new(limits.per_hour, 60 * 60),
+            last: Instant::now(),
+        }
+    }
+
+    /// Refills the buckets, and returns whether another event may be read.
+    #[inline]
+    pub fn ready(&mut self) -> bool {
+        self.ready_at(Instant::now())
+    }
+
+    #[inline]
+    fn ready_at(&mut self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.second.refill(elapsed);
+        self.hour.refill(elapsed);
+        self.second.tokens >= 1.0 && self.hour.tokens >= 1.0
+    }
+
+    #[inline]
+    pub fn take(&mut self) {
+        self.second.tokens -= 1.0;
+        self.hour.tokens -= 1.0;
+    }
+
+    #[inline]
+    pub fn exhausted(&self) -> bool {
+        self.hour.tokens < 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::crawler::limiter::RateLimiter;
+    use crate::crawler::types::RateLimits;
+
+    // reads as many events as the limiter allows at `now`
+    fn drain(limiter: &mut RateLimiter, now: Instant) -> u32 {
+        let mut events = 0;
+        while limiter.ready_at(now) {
+            limiter.take();
+            events += 1;
+        }
+        events
+    }
+
+    #[test]
+    fn burst_and_refill() {
+        let mut limiter = RateLimiter::new(RateLimits { per_second: 10, per_hour: 10_000 });
+        let start = Instant::now();
+        assert_eq!(drain(&mut limiter, start), 10);
+        assert_eq!(drain(&mut limiter, start + Duration::from_millis(500)), 5);
+        // an idle host only gets a second's worth of burst
+        assert_eq!(drain(&mut limiter, start + Duration::from_secs(60)), 10);
+        assert!(!limiter.exhausted());
+    }
+
+    #[test]
+    fn per_hour() {
+        let mut limiter = RateLimiter::new(RateLimits { per_second: 1000, per_hour: 5 });
+        let start = Instant::now();
+        assert_eq!(drain(&mut limiter, start), 5);
+        assert!(limiter.exhausted());
+        assert!(!limiter.ready_at(start + Duration::from_secs(60)));
+        assert!(limiter.exhausted());
+        // one event every 720s
+        assert_eq!(drain(&mut limiter, start + Duration::from_secs(721)), 1);
+        assert!(limiter.exhausted());
+    }
+
+    #[test]
+    fn zero_limits() {
+        let mut limiter = RateLimiter::new(RateLimits { per_second: 0, per_hour: 0 });
+        assert_eq!(drain(&mut limiter, Instant::now()), 1);
+    }
+
+    #[test]
+    fn account_scaling() {
+        let limits = RateLimits { per_second: 50, per_hour: 2500 };
+        let scaled = limits.for_accounts(0);
+        assert_eq!((scaled.per_second, scaled.per_hour), (50, 2500));
+        let scaled = limits.for_accounts(999);
+        assert_eq!((scaled.per_second, scaled.per_hour), (50, 3499));
+        let scaled = limits.for_accounts(250_000);
+        assert_eq!((scaled.per_second, scaled.per_hour), (300, 252_500));
+        let scaled = limits.for_accounts(u64::MAX);
+        assert_eq!((scaled.per_second, scaled.per_hour), (50 + u32::MAX / 1000, u32::MAX));
+    }
+}
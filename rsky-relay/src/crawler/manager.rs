@@ -3,6 +3,8 @@ use std::sync::atomic::Ordering;
 use std::time::{Duration, Instant};
 use std::{io, thread};
 
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use exponential_backoff::{Backoff, IntoIter as BackoffIter};
 use hashbrown::HashMap;
 use magnetic::Consumer;
@@ -11,11 +13,19 @@ use rusqlite::{Connection, ErrorCode, OpenFlags, OptionalExtension};
 use thiserror::Error;
 
 use crate::SHUTDOWN;
-use crate::config::CAPACITY_STATUS;
+use crate::config::{
+    CAPACITY_STATUS, Config, HOSTS_OFFLINE_FAILURES, HOSTS_REBALANCE_INTERVAL,
+    HOSTS_REBALANCE_SPREAD, HOSTS_WRITE_INTERVAL,
+};
 use crate::crawler::RequestCrawl;
-use crate::crawler::types::{Command, CommandSender, RequestCrawlReceiver, Status, StatusReceiver};
+use crate::crawler::dns::Dns;
+use crate::crawler::types::{
+    AdminCommand, AdminReceiver, Command, CommandSender, CursorReceiver, Load, RateLimits,
+    RequestCrawlReceiver, Status, StatusReceiver,
+};
 use crate::crawler::worker::{Worker, WorkerError};
-use crate::types::{Cursor, MessageSender};
+use crate::metrics::METRICS;
+use crate::types::{Cursor, HostStatus, MessageSender, open_relay_db};
 
 const SLEEP: Duration = Duration::from_millis(10);
 
@@ -43,50 +53,176 @@ impl From<rtrb::PushError<Command>> for ManagerError {
 struct WorkerHandle {
     pub command_tx: CommandSender,
     pub thread_handle: thread::JoinHandle<Result<(), WorkerError>>,
+    // last reported load, plus estimates for hosts assigned since
+    pub load: Load,
+    pub hosts: usize,
+}
+
+struct HostState {
+    status: HostStatus,
+    // worker the host was last assigned to
+    worker_id: Option<usize>,
+    // consecutive failed connection attempts
+    failures: u32,
+    backoff: [BackoffIter; 2],
+    // next scheduled connection attempt
+    retry_at: Option<DateTime<Utc>>,
+    // last reported load, kept across reconnects
+    load: Load,
+    // status or backoff not yet written to relay.db
+    unsaved: bool,
+    // moving to another worker, or ended by an error frame, reconnected once the validator handled
+    // the old connection's events
+    draining: bool,
+}
+
+impl HostState {
+    // picks up where the backoff left off before a restart
+    fn restore(status: HostStatus, failures: u32, retry_at: Option<DateTime<Utc>>) -> Self {
+        let mut this = Self::new(status);
+        this.failures = failures;
+        this.retry_at = retry_at;
+        this.backoff[0].by_ref().take(failures as usize).for_each(drop);
+        this
+    }
+
+    // time left until the scheduled attempt
+    fn retry_in(&self) -> Duration {
+        self.retry_at.and_then(|at| (at - Utc::now()).to_std().ok()).unwrap_or_default()
+    }
+
+    fn new(status: HostStatus) -> Self {
+        let backoff_connect =
+            Backoff::new(u32::MAX, Duration::from_secs(60), Duration::from_secs(60 * 60 * 6));
+        let backoff_reconnect =
+            Backoff::new(u32::MAX, Duration::from_secs(1), Duration::from_secs(60 * 60));
+        Self {
+            status,
+            worker_id: None,
+            failures: 0,
+            backoff: [backoff_connect.iter(), backoff_reconnect.iter()],
+            retry_at: None,
+            load: Load::default(),
+            unsaved: false,
+            draining: false,
+        }
+    }
 }
 
 pub struct Manager {
     workers: Box<[WorkerHandle]>,
-    next_id: usize,
-    hosts: HashMap<String, [BackoffIter; 2]>,
-    retries: BTreeMap<Instant, (usize, String)>,
+    dns_handle: thread::JoinHandle<io::Result<()>>,
+    hosts: HashMap<String, HostState>,
+    retries: BTreeMap<Instant, RequestCrawl>,
+    limits: RateLimits,
+    rebalance: bool,
+    last_rebalance: Instant,
+    last_persist: Instant,
     conn: Connection,
+    message_tx: MessageSender,
     request_crawl_rx: RequestCrawlReceiver,
+    admin_rx: AdminReceiver,
     status_rx: StatusReceiver,
+    cursor_rx: CursorReceiver,
 }
 
 impl Manager {
     pub fn new(
-        n_workers: usize, message_tx: &MessageSender, request_crawl_rx: RequestCrawlReceiver,
+        config: &Config, message_tx: &MessageSender, request_crawl_rx: RequestCrawlReceiver,
+        admin_rx: AdminReceiver, cursor_rx: CursorReceiver,
     ) -> Result<Self, ManagerError> {
         #[expect(clippy::unwrap_used)]
         let (status_tx, status_rx) =
             magnetic::mpsc::mpsc_queue(DynamicBufferP2::new(CAPACITY_STATUS).unwrap());
-        let workers = (0..n_workers)
-            .map(|worker_id| -> Result<_, ManagerError> {
+        METRICS.messages_capacity.store(message_tx.capacity() as u64, Ordering::Relaxed);
+        let (dns, lookup_tx, resolved_rx) = Dns::new(config);
+        let dns_handle = thread::Builder::new().name("rsky-dns".into()).spawn(move || dns.run())?;
+        let tls = !config.dev_mode;
+        if config.dev_mode {
+            tracing::warn!("dev mode, crawling hosts over plain ws://");
+        }
+        let workers = resolved_rx
+            .into_iter()
+            .enumerate()
+            .map(|(worker_id, resolved_rx)| -> Result<_, ManagerError> {
                 let message_tx = message_tx.clone();
                 let status_tx = status_tx.clone();
+                let lookup_tx = lookup_tx.clone();
                 let (command_tx, command_rx) = rtrb::RingBuffer::new(CAPACITY_STATUS);
-                let thread_handle =
-                    thread::Builder::new().name(format!("rsky-crawl-{worker_id}")).spawn(
-                        move || Worker::new(worker_id, message_tx, command_rx, status_tx)?.run(),
-                    )?;
-                Ok(WorkerHandle { command_tx, thread_handle })
+                let thread_handle = thread::Builder::new()
+                    .name(format!("rsky-crawl-{worker_id}"))
+                    .spawn(move || {
+                        Worker::new(
+                            worker_id,
+                            message_tx,
+                            command_rx,
+                            status_tx,
+                            lookup_tx,
+                            resolved_rx,
+                            tls,
+                        )?
+                        .run()
+                    })?;
+                Ok(WorkerHandle { command_tx, thread_handle, load: Load::default(), hosts: 0 })
             })
             .collect::<Result<Vec<_>, _>>()?;
-        let conn = Connection::open_with_flags(
-            "relay.db",
-            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
-        )?;
-        Ok(Self {
+        let conn = open_relay_db(config, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+        let mut this = Self {
             workers: workers.into_boxed_slice(),
-            next_id: 0,
+            dns_handle,
             hosts: HashMap::new(),
             retries: BTreeMap::new(),
+            limits: RateLimits {
+                per_second: config.host_events_per_second,
+                per_hour: config.host_events_per_hour,
+            },
+            rebalance: config.crawler_rebalance,
+            last_rebalance: Instant::now(),
+            last_persist: Instant::now(),
             conn,
+            message_tx: message_tx.clone(),
             request_crawl_rx,
+            admin_rx,
             status_rx,
-        })
+            cursor_rx,
+        };
+        this.restore()?;
+        Ok(this)
+    }
+
+    fn restore(&mut self) -> Result<(), ManagerError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT host, status, failures, retry_at FROM hosts
+             WHERE failures > 0 OR retry_at IS NOT NULL",
+        )?;
+        let rows = stmt
+            .query_map((), |row| {
+                Ok((
+                    row.get::<_, String>("host")?,
+                    row.get::<_, HostStatus>("status")?,
+                    row.get::<_, u32>("failures")?,
+                    row.get::<_, Option<DateTime<Utc>>>("retry_at")?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        drop(stmt);
+
+        let mut scheduled = 0;
+        let restored = rows.len();
+        let now = Instant::now();
+        for (hostname, status, failures, retry_at) in rows {
+            self.hosts.insert(hostname.clone(), HostState::restore(status, failures, retry_at));
+            // hosts that were given up on wait for their next requestCrawl
+            let Some(retry_at) = retry_at else { continue };
+            if matches!(status, HostStatus::Offline | HostStatus::Banned) {
+                continue;
+            }
+            let delay = (retry_at - Utc::now()).to_std().unwrap_or_default();
+            self.schedule(now + delay, RequestCrawl { hostname, cursor: None });
+            scheduled += 1;
+        }
+        tracing::info!(%restored, %scheduled, "restored crawler backoff");
+        Ok(())
     }
 
     pub fn run(mut self) -> Result<(), ManagerError> {
@@ -104,6 +240,10 @@ impl Manager {
                 tracing::warn!(%id, %err, "crawler worker error");
             }
         }
+        // exits once every worker dropped its lookup sender
+        if let Err(err) = self.dns_handle.join().map_err(|_| ManagerError::Join)? {
+            tracing::warn!(%err, "crawler dns error");
+        }
         Ok(())
     }
 
@@ -114,72 +254,512 @@ impl Manager {
 
         if let Some(entry) = self.retries.first_entry() {
             if *entry.key() < Instant::now() {
-                let (id, hostname) = entry.remove();
-                let prev = self.next_id;
-                self.next_id = id;
-                self.handle_connect(RequestCrawl { hostname, cursor: None })?;
-                self.next_id = prev;
+                let request_crawl = entry.remove();
+                self.handle_connect(request_crawl)?;
             }
         }
 
+        if self.rebalance && self.last_rebalance.elapsed() > HOSTS_REBALANCE_INTERVAL {
+            self.rebalance()?;
+            self.last_rebalance = Instant::now();
+        }
+
+        if self.last_persist.elapsed() > HOSTS_WRITE_INTERVAL {
+            self.persist_unsaved();
+            self.last_persist = Instant::now();
+        }
+
         if let Ok(status) = self.status_rx.try_pop() {
             self.handle_status(status);
         }
 
         if let Ok(request_crawl) = self.request_crawl_rx.pop() {
-            if !self.hosts.contains_key(&request_crawl.hostname) {
-                self.handle_connect(request_crawl)?;
+            match self.hosts.get(&request_crawl.hostname).map(|host| host.status) {
+                // offline hosts get another attempt, banned hosts are re-checked against the db
+                None | Some(HostStatus::Offline | HostStatus::Banned) => {
+                    self.handle_connect(request_crawl)?;
+                }
+                Some(_) => {}
+            }
+        }
+
+        if let Ok(command) = self.admin_rx.pop() {
+            self.handle_admin(command)?;
+        }
+
+        if let Ok((hostname, cursor)) = self.cursor_rx.pop() {
+            if let Some(host) = self.hosts.get_mut(&hostname).filter(|host| host.draining) {
+                host.draining = false;
+                // errored hosts still wait for their backoff
+                let at = Instant::now() + host.retry_in();
+                self.schedule(at, RequestCrawl { hostname, cursor });
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn handle_admin(&mut self, command: AdminCommand) -> Result<(), ManagerError> {
+        match command {
+            AdminCommand::Ban(hostname) => {
+                self.set_status(&hostname, HostStatus::Banned);
+                self.disconnect(&hostname)?;
+            }
+            AdminCommand::Unban(hostname) => {
+                // unbanned hosts wait for the next requestCrawl, like offline ones
+                let status = self.get_host(&hostname)?.map(|(_, status)| status);
+                if status == Some(HostStatus::Banned) {
+                    if let Some(host) = self.hosts.get_mut(&hostname) {
+                        host.failures = 0;
+                    }
+                    self.set_status(&hostname, HostStatus::Offline);
+                    self.set_retry(&hostname, None);
+                }
+            }
+            AdminCommand::Disconnect(hostname) => {
+                if self.disconnect(&hostname)? {
+                    self.set_status(&hostname, HostStatus::Offline);
+                }
             }
         }
+        Ok(())
+    }
 
+    // false: not assigned to a worker
+    // true: disconnect sent
+    fn disconnect(&mut self, hostname: &str) -> Result<bool, ManagerError> {
+        let Some(host) = self.hosts.get_mut(hostname) else {
+            return Ok(false);
+        };
+        host.draining = false;
+        let Some(worker_id) = host.worker_id.take() else {
+            return Ok(false);
+        };
+        self.retries.retain(|_, retry| retry.hostname != hostname);
+        self.workers[worker_id].command_tx.push(Command::Disconnect(hostname.to_owned()))?;
         Ok(true)
     }
 
     fn handle_status(&mut self, status: Status) {
         match status {
-            Status::Disconnected { worker_id: id, hostname, connected } => {
+            Status::Connected { worker_id: id, hostname } => {
+                let Some(host) = self.hosts.get_mut(&hostname) else { return };
+                if host.worker_id != Some(id) {
+                    return;
+                }
+                host.failures = 0;
+                let status = host.status;
+                self.set_retry(&hostname, None);
+                if matches!(status, HostStatus::Offline | HostStatus::Idle | HostStatus::Throttled)
+                {
+                    self.set_status(&hostname, HostStatus::Active);
+                }
+            }
+            Status::Disconnected { worker_id: id, hostname, connected, errored } => {
+                #[expect(clippy::unwrap_used)]
+                let host = self.hosts.get_mut(&hostname).unwrap();
+                if host.worker_id != Some(id) || host.status == HostStatus::Banned {
+                    // disconnected by an admin in the meantime
+                    return;
+                }
+                if host.draining {
+                    self.drain(hostname);
+                    return;
+                }
+                if !connected {
+                    host.failures += 1;
+                    if host.failures >= HOSTS_OFFLINE_FAILURES {
+                        let failures = host.failures;
+                        tracing::info!(host = %hostname, %failures, "giving up on host");
+                        self.set_status(&hostname, HostStatus::Offline);
+                        self.set_retry(&hostname, None);
+                        return;
+                    }
+                }
                 #[expect(clippy::unwrap_used)]
-                let backoff =
-                    self.hosts.get_mut(&hostname).unwrap().get_mut(usize::from(connected)).unwrap();
+                let backoff = host.backoff.get_mut(usize::from(connected)).unwrap();
                 let Some(Some(delay)) = backoff.next() else { unreachable!() };
-                let next = Instant::now() + delay;
-                assert!(self.retries.insert(next, (id, hostname)).is_none());
+                host.draining = errored;
+                self.set_retry(&hostname, Some(Utc::now() + delay));
+                if errored {
+                    // eg a `FutureCursor`, which the validator answers by resetting its cursor
+                    self.drain(hostname);
+                    return;
+                }
+                self.schedule(Instant::now() + delay, RequestCrawl { hostname, cursor: None });
+            }
+            Status::Idle { worker_id: id, hostname, idle } => {
+                let Some(host) = self.hosts.get(&hostname) else { return };
+                if host.worker_id != Some(id) {
+                    return;
+                }
+                match (host.status, idle) {
+                    (HostStatus::Active, true) => self.set_status(&hostname, HostStatus::Idle),
+                    (HostStatus::Idle, false) => self.set_status(&hostname, HostStatus::Active),
+                    _ => {}
+                }
+            }
+            Status::Throttled { worker_id: id, hostname, throttled } => {
+                let Some(host) = self.hosts.get(&hostname) else { return };
+                if host.worker_id != Some(id) {
+                    return;
+                }
+                match (host.status, throttled) {
+                    (HostStatus::Active | HostStatus::Idle, true) => {
+                        self.set_status(&hostname, HostStatus::Throttled);
+                    }
+                    (HostStatus::Throttled, false) => {
+                        self.set_status(&hostname, HostStatus::Active);
+                    }
+                    _ => {}
+                }
+            }
+            Status::Load { worker_id: id, hosts } => {
+                let mut total = Load::default();
+                for (hostname, load) in &hosts {
+                    total.events += load.events;
+                    total.bytes += load.bytes;
+                    if let Some(host) = self.hosts.get_mut(hostname) {
+                        if host.worker_id == Some(id) {
+                            host.load = *load;
+                        }
+                    }
+                }
+                let worker = &mut self.workers[id];
+                worker.load = total;
+                worker.hosts = hosts.len();
             }
         }
     }
 
     fn handle_connect(&mut self, mut request_crawl: RequestCrawl) -> Result<(), ManagerError> {
-        self.hosts.entry(request_crawl.hostname.clone()).or_insert_with(|| {
-            let backoff_connect =
-                Backoff::new(u32::MAX, Duration::from_secs(60), Duration::from_secs(60 * 60 * 6));
-            let backoff_reconnect =
-                Backoff::new(u32::MAX, Duration::from_secs(1), Duration::from_secs(60 * 60));
-            [backoff_connect.iter(), backoff_reconnect.iter()]
-        });
-        if request_crawl.cursor.is_none() {
-            request_crawl.cursor = loop {
-                match self.get_cursor(&request_crawl.hostname) {
-                    Ok(cursor) => break cursor,
-                    Err(ManagerError::Sqlite(err))
-                        if err.sqlite_error_code() == Some(ErrorCode::DatabaseLocked) =>
-                    {
-                        continue;
-                    }
-                    Err(err) => Err(err)?,
+        let worker_id = self.least_loaded();
+        let average = self.average_load();
+        let (cursor, status) = loop {
+            match self.get_host(&request_crawl.hostname) {
+                Ok(host) => break host.unzip(),
+                Err(ManagerError::Sqlite(err))
+                    if err.sqlite_error_code() == Some(ErrorCode::DatabaseLocked) =>
+                {
+                    continue;
                 }
-            };
+                Err(err) => Err(err)?,
+            }
+        };
+        let status = status.unwrap_or_default();
+        let host = self
+            .hosts
+            .entry(request_crawl.hostname.clone())
+            .or_insert_with(|| HostState::new(status));
+        host.status = status;
+        if status == HostStatus::Banned {
+            tracing::info!(host = %request_crawl.hostname, "refusing to crawl banned host");
+            return Ok(());
+        }
+        host.worker_id = Some(worker_id);
+        // counted until the worker's next report, guessing the average for unknown hosts
+        let estimate = if host.load.bytes > 0.0 { host.load } else { average };
+        let worker = &mut self.workers[worker_id];
+        worker.load.events += estimate.events;
+        worker.load.bytes += estimate.bytes;
+        worker.hosts += 1;
+        if request_crawl.cursor.is_none() {
+            // a zero cursor means the host has never sent an event
+            request_crawl.cursor = cursor.filter(|cursor| cursor.get() != 0);
         }
-        self.workers[self.next_id].command_tx.push(Command::Connect(request_crawl))?;
-        self.next_id = (self.next_id + 1) % self.workers.len();
+        let limits = self.get_limits(&request_crawl.hostname).unwrap_or_else(|err| {
+            tracing::warn!(host = %request_crawl.hostname, %err, "unable to read host limits");
+            self.limits
+        });
+        self.workers[worker_id].command_tx.push(Command::Connect(request_crawl, limits))?;
         thread::sleep(SLEEP);
         Ok(())
     }
 
-    fn get_cursor(&self, host: &str) -> Result<Option<Cursor>, ManagerError> {
+    fn schedule(&mut self, mut at: Instant, request_crawl: RequestCrawl) {
+        while self.retries.contains_key(&at) {
+            at += Duration::from_nanos(1);
+        }
+        self.retries.insert(at, request_crawl);
+    }
+
+    fn loads(&self) -> Vec<WorkerLoad> {
+        self.workers.iter().map(|worker| (worker.load, worker.hosts)).collect()
+    }
+
+    fn least_loaded(&self) -> usize {
+        least_loaded(&self.loads())
+    }
+
+    fn average_load(&self) -> Load {
+        average_load(&self.loads())
+    }
+
+    // disconnects one host from the busiest worker, it's moved once the worker reports it gone
+    fn rebalance(&mut self) -> Result<(), ManagerError> {
+        let Some(Migration { hostname, from, to, load }) = migration(&self.loads(), &self.hosts)
+        else {
+            return Ok(());
+        };
+        tracing::info!(host = %hostname, %from, %to, bytes = %load.bytes, "migrating host");
+        self.retries.retain(|_, retry| retry.hostname != hostname);
+        self.workers[from].command_tx.push(Command::Disconnect(hostname.clone()))?;
+        if let Some(host) = self.hosts.get_mut(&hostname) {
+            host.draining = true;
+        }
+        let worker = &mut self.workers[from];
+        worker.load.events -= load.events;
+        worker.load.bytes -= load.bytes;
+        worker.hosts = worker.hosts.saturating_sub(1);
+        Ok(())
+    }
+
+    // asks the validator for the host's cursor behind every event the old connection read, an
+    // empty message marking the end of them
+    fn drain(&mut self, hostname: String) {
+        if let Ok(mut slot) = self.message_tx.try_send_ref() {
+            slot.data = Bytes::new();
+            slot.hostname.clone_from(&hostname);
+            return;
+        }
+        // the validator is backed up, reconnect at the saved cursor
+        tracing::warn!(host = %hostname, "message channel full, skipping the validator's cursor");
+        let Some(host) = self.hosts.get_mut(&hostname) else { return };
+        host.draining = false;
+        let at = Instant::now() + host.retry_in();
+        self.schedule(at, RequestCrawl { hostname, cursor: None });
+    }
+
+    fn set_status(&mut self, hostname: &str, status: HostStatus) {
+        if let Some(host) = self.hosts.get_mut(hostname) {
+            if host.status == status {
+                return;
+            }
+            tracing::info!(host = %hostname, from = %host.status, to = %status, "host status changed");
+            host.status = status;
+        } else {
+            tracing::info!(host = %hostname, to = %status, "host status changed");
+            self.hosts.insert(hostname.to_owned(), HostState::new(status));
+        }
+        self.persist_host(hostname);
+    }
+
+    fn set_retry(&mut self, hostname: &str, retry_at: Option<DateTime<Utc>>) {
+        if let Some(host) = self.hosts.get_mut(hostname) {
+            host.retry_at = retry_at;
+            self.persist_host(hostname);
+        }
+    }
+
+    fn get_host(&self, host: &str) -> Result<Option<(Cursor, HostStatus)>, ManagerError> {
         let mut stmt = self.conn.prepare_cached("SELECT * FROM hosts WHERE host = ?1")?;
         Ok(stmt
-            .query_one((&host,), |row| Ok(row.get_unwrap::<_, u64>("cursor")))
-            .optional()?
-            .map(Into::into))
+            .query_one((&host,), |row| {
+                Ok((row.get_unwrap::<_, u64>("cursor").into(), row.get_unwrap("status")))
+            })
+            .optional()?)
+    }
+
+    // defaults scaled by the host's account count, unless overridden in `host_limits`
+    // only read on connect, the worker keeps the limits for the lifetime of the connection
+    fn get_limits(&self, host: &str) -> Result<RateLimits, ManagerError> {
+        let mut stmt = self.conn.prepare_cached("SELECT accounts FROM hosts WHERE host = ?1")?;
+        let accounts: u64 = stmt.query_one((&host,), |row| row.get(0)).optional()?.unwrap_or(0);
+        let mut limits = self.limits.for_accounts(accounts);
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT per_second, per_hour FROM host_limits WHERE host = ?1")?;
+        let overrides = stmt
+            .query_one((&host,), |row| {
+                Ok((row.get::<_, Option<u32>>(0)?, row.get::<_, Option<u32>>(1)?))
+            })
+            .optional()?;
+        if let Some((per_second, per_hour)) = overrides {
+            limits.per_second = per_second.unwrap_or(limits.per_second);
+            limits.per_hour = per_hour.unwrap_or(limits.per_hour);
+        }
+        Ok(limits)
+    }
+
+    // a failed write is retried from `update`, so status changes are never dropped
+    fn persist_host(&mut self, hostname: &str) {
+        let Some(host) = self.hosts.get_mut(hostname) else { return };
+        let res = self
+            .conn
+            .prepare_cached(
+                "
+                    INSERT INTO hosts (host, cursor, latest, status, failures, retry_at)
+                    VALUES (?1, 0, ?2, ?3, ?4, ?5)
+                    ON CONFLICT(host)
+                    DO UPDATE SET
+                        status = excluded.status,
+                        failures = excluded.failures,
+                        retry_at = excluded.retry_at
+                ",
+            )
+            .and_then(|mut stmt| {
+                stmt.execute((
+                    hostname,
+                    DateTime::<Utc>::UNIX_EPOCH,
+                    host.status,
+                    host.failures,
+                    host.retry_at,
+                ))
+            });
+        host.unsaved = res.is_err();
+        if let Err(err) = res {
+            tracing::warn!(host = %hostname, %err, "unable to persist host state");
+        }
+    }
+
+    fn persist_unsaved(&mut self) {
+        let unsaved = self
+            .hosts
+            .iter()
+            .filter(|(_, host)| host.unsaved)
+            .map(|(hostname, _)| hostname.clone())
+            .collect::<Vec<_>>();
+        for hostname in unsaved {
+            self.persist_host(&hostname);
+        }
+    }
+}
+
+// the worker reading the fewest bytes, then the one with the fewest hosts
+// each worker's load and host count
+type WorkerLoad = (Load, usize);
+
+fn least_loaded(workers: &[WorkerLoad]) -> usize {
+    workers
+        .iter()
+        .enumerate()
+        .min_by(|(_, (a, a_hosts)), (_, (b, b_hosts))| {
+            a.bytes.total_cmp(&b.bytes).then(a_hosts.cmp(b_hosts))
+        })
+        .map_or(0, |(id, _)| id)
+}
+
+#[expect(clippy::cast_precision_loss)]
+fn average_load(workers: &[WorkerLoad]) -> Load {
+    let hosts = workers.iter().map(|(_, hosts)| hosts).sum::<usize>().max(1) as f64;
+    let mut load = Load::default();
+    for (worker, _) in workers {
+        load.events += worker.events / hosts;
+        load.bytes += worker.bytes / hosts;
+    }
+    load
+}
+
+#[derive(Debug)]
+struct Migration {
+    hostname: String,
+    from: usize,
+    to: usize,
+    load: Load,
+}
+
+// the host of the busiest worker that brings it and the idlest closest to even, if they're far
+// enough apart
+fn migration(workers: &[WorkerLoad], hosts: &HashMap<String, HostState>) -> Option<Migration> {
+    let by_bytes = |(_, (a, _)): &(usize, &WorkerLoad), (_, (b, _)): &(usize, &WorkerLoad)| {
+        a.bytes.total_cmp(&b.bytes)
+    };
+    let (from, (max, _)) = workers.iter().enumerate().max_by(by_bytes)?;
+    let (to, (min, _)) = workers.iter().enumerate().min_by(by_bytes)?;
+    let gap = max.bytes - min.bytes;
+    if gap <= max.bytes * HOSTS_REBALANCE_SPREAD {
+        return None;
+    }
+    let target = gap / 2.0;
+    hosts
+        .iter()
+        .filter(|(_, host)| {
+            host.worker_id == Some(from)
+                && host.status == HostStatus::Active
+                && !host.draining
+                && host.load.bytes > 0.0
+                && host.load.bytes < gap
+        })
+        .min_by(|(_, a), (_, b)| {
+            (a.load.bytes - target).abs().total_cmp(&(b.load.bytes - target).abs())
+        })
+        .map(|(hostname, host)| Migration { hostname: hostname.clone(), from, to, load: host.load })
+}
+
+#[cfg(test)]
+mod tests {
+    use hashbrown::HashMap;
+
+    use crate::crawler::manager::{
+        HostState, Migration, WorkerLoad, average_load, least_loaded, migration,
+    };
+    use crate::crawler::types::Load;
+    use crate::types::HostStatus;
+
+    const fn worker(bytes: f64, hosts: usize) -> WorkerLoad {
+        (Load { events: bytes / 100.0, bytes }, hosts)
+    }
+
+    fn host(worker_id: usize, bytes: f64) -> HostState {
+        let mut host = HostState::new(HostStatus::Active);
+        host.worker_id = Some(worker_id);
+        host.load = Load { events: bytes / 100.0, bytes };
+        host
+    }
+
+    #[test]
+    fn loads() {
+        let workers = [worker(300.0, 3), worker(100.0, 2), worker(100.0, 1), worker(500.0, 4)];
+        // ties on bytes go to the worker with the fewest hosts
+        assert_eq!(least_loaded(&workers), 2);
+        let load = average_load(&workers);
+        assert!((load.bytes - 100.0).abs() < f64::EPSILON);
+        assert!((load.events - 1.0).abs() < f64::EPSILON);
+
+        assert_eq!(least_loaded(&[]), 0);
+        let load = average_load(&[]);
+        assert!(load.bytes.abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn migrations() {
+        let workers = [worker(1000.0, 3), worker(200.0, 1)];
+        let mut hosts = HashMap::new();
+        hosts.insert("big.test".to_owned(), host(0, 700.0));
+        hosts.insert("mid.test".to_owned(), host(0, 250.0));
+        hosts.insert("small.test".to_owned(), host(0, 50.0));
+        hosts.insert("other.test".to_owned(), host(1, 200.0));
+
+        // the gap is 800, so the host closest to 400 evens them out best
+        let Some(Migration { hostname, from, to, load }) = migration(&workers, &hosts) else {
+            panic!("expected a migration");
+        };
+        assert_eq!((hostname.as_str(), from, to), ("mid.test", 0, 1));
+        assert!((load.bytes - 250.0).abs() < f64::EPSILON);
+
+        // hosts already moving, or not active, are left alone
+        if let Some(host) = hosts.get_mut("mid.test") {
+            host.draining = true;
+        }
+        if let Some(host) = hosts.get_mut("big.test") {
+            host.status = HostStatus::Banned;
+        }
+        let migration = migration(&workers, &hosts).map(|m| m.hostname);
+        assert_eq!(migration.as_deref(), Some("small.test"));
+    }
+
+    #[test]
+    fn balanced() {
+        let mut hosts = HashMap::new();
+        hosts.insert("a.test".to_owned(), host(0, 150.0));
+        hosts.insert("b.test".to_owned(), host(1, 100.0));
+        // within the spread
+        let workers = [worker(150.0, 1), worker(100.0, 1)];
+        assert!(migration(&workers, &hosts).is_none());
+        // a host as big as the gap would only swap the imbalance
+        let workers = [worker(1000.0, 1), worker(100.0, 1)];
+        hosts.insert("a.test".to_owned(), host(0, 1000.0));
+        assert!(migration(&workers, &hosts).is_none());
+        assert!(migration(&[], &hosts).is_none());
     }
 }
@@ -1,8 +1,12 @@
 mod client;
 mod connection;
+mod dns;
+mod host;
+mod limiter;
 mod manager;
 mod types;
 mod worker;
 
+pub use host::{HostnameError, is_public, split_port, validate_hostname};
 pub use manager::{Manager, ManagerError};
-pub use types::{RequestCrawl, RequestCrawlSender};
+pub use types::{AdminCommand, AdminSender, CursorSender, RequestCrawl, RequestCrawlSender};
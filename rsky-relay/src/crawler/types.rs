@@ -1,14 +1,16 @@
-use std::net::TcpStream;
+use std::net::{IpAddr, TcpStream};
 
 use magnetic::buffer::dynamic::DynamicBufferP2;
 use magnetic::mpsc::{MPSCConsumer, MPSCProducer};
 use rtrb::{Consumer, Producer};
 use serde::Deserialize;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use tungstenite::handshake::MidHandshake;
 use tungstenite::handshake::client::Response;
 use tungstenite::stream::MaybeTlsStream;
 use tungstenite::{ClientHandshake, HandshakeError, WebSocket};
 
+use crate::crawler::dns::DnsError;
 use crate::types::Cursor;
 
 pub type MaybeTlsTcpStream = MaybeTlsStream<TcpStream>;
@@ -39,6 +41,15 @@ pub type StatusSender = MPSCProducer<Status, DynamicBufferP2<Status>>;
 pub type StatusReceiver = MPSCConsumer<Status, DynamicBufferP2<Status>>;
 pub type RequestCrawlSender = Producer<RequestCrawl>;
 pub type RequestCrawlReceiver = Consumer<RequestCrawl>;
+pub type AdminSender = Producer<AdminCommand>;
+pub type AdminReceiver = Consumer<AdminCommand>;
+pub type LookupSender = UnboundedSender<(usize, String)>;
+pub type LookupReceiver = UnboundedReceiver<(usize, String)>;
+pub type ResolvedSender = Producer<Resolved>;
+pub type ResolvedReceiver = Consumer<Resolved>;
+/// The validator's cursor for a host, `None` if it never saw an event from it.
+pub type CursorSender = Producer<(String, Option<Cursor>)>;
+pub type CursorReceiver = Consumer<(String, Option<Cursor>)>;
 
 #[derive(Debug, Deserialize)]
 pub struct RequestCrawl {
@@ -47,12 +58,57 @@ pub struct RequestCrawl {
     pub cursor: Option<Cursor>,
 }
 
+#[derive(Debug)]
+pub struct Resolved {
+    pub hostname: String,
+    pub addrs: Result<Vec<IpAddr>, DnsError>,
+}
+
+#[derive(Debug)]
+pub enum AdminCommand {
+    Ban(String),
+    Unban(String),
+    Disconnect(String),
+}
+
+/// Events per second and per hour read from a single host.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimits {
+    pub per_second: u32,
+    pub per_hour: u32,
+}
+
+impl RateLimits {
+    /// Raised by one event per second per thousand accounts, and one per hour per account.
+    #[must_use]
+    pub fn for_accounts(self, accounts: u64) -> Self {
+        let accounts = u32::try_from(accounts).unwrap_or(u32::MAX);
+        Self {
+            per_second: self.per_second.saturating_add(accounts / 1000),
+            per_hour: self.per_hour.saturating_add(accounts),
+        }
+    }
+}
+
+/// Events and bytes per second read from a host, averaged since the previous report.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Load {
+    pub events: f64,
+    pub bytes: f64,
+}
+
 #[derive(Debug)]
 pub enum Command {
-    Connect(RequestCrawl),
+    Connect(RequestCrawl, RateLimits),
+    Disconnect(String),
 }
 
 #[derive(Debug)]
 pub enum Status {
-    Disconnected { worker_id: usize, hostname: String, connected: bool },
+    Connected { worker_id: usize, hostname: String },
+    // errored: the host sent an error frame, reconnect from the validator's cursor
+    Disconnected { worker_id: usize, hostname: String, connected: bool, errored: bool },
+    Idle { worker_id: usize, hostname: String, idle: bool },
+    Throttled { worker_id: usize, hostname: String, throttled: bool },
+    Load { worker_id: usize, hosts: Vec<(String, Load)> },
 }
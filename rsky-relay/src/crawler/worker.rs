@@ -10,14 +10,20 @@ use mio::{Events, Interest, Poll, Token};
 use thiserror::Error;
 
 use crate::SHUTDOWN;
+use crate::config::{HOSTS_IDLE, HOSTS_IDLE_CHECK};
+use crate::crawler::client::Connecting;
 use crate::crawler::connection::{Connection, ConnectionError};
 use crate::crawler::types::{
-    Command, CommandReceiver, DecomposeError, HandshakeResult, Handshaking, Status, StatusSender,
+    Command, CommandReceiver, DecomposeError, HandshakeResult, Handshaking, LookupSender,
+    RateLimits, RequestCrawl, Resolved, ResolvedReceiver, Status, StatusSender,
 };
+use crate::metrics::{METRICS, Metrics};
 use crate::types::MessageSender;
 
 const INTEREST: Interest = Interest::READABLE;
 const TIMEOUT: Duration = Duration::from_secs(5);
+/// Tokens with this bit set refer to `connecting` slots rather than `connections`.
+const CONNECTING: usize = 1 << (usize::BITS - 1);
 
 #[derive(Debug, Error)]
 pub enum WorkerError {
@@ -29,12 +35,19 @@ pub enum WorkerError {
 
 pub struct Worker {
     id: usize,
-    pending: VecDeque<(Instant, String, Handshaking)>,
+    // false in dev mode, where hosts are crawled over plain ws://
+    tls: bool,
+    resolving: Vec<(Instant, RequestCrawl, RateLimits)>,
+    connecting: Vec<Option<(Instant, String, RateLimits, Connecting)>>,
+    pending: VecDeque<(Instant, String, RateLimits, Handshaking)>,
     connections: Vec<Option<Connection>>,
     next_idx: usize,
+    last_check: Instant,
     message_tx: MessageSender,
     command_rx: CommandReceiver,
     status_tx: StatusSender,
+    lookup_tx: LookupSender,
+    resolved_rx: ResolvedReceiver,
     poll: Poll,
     events: Events,
 }
@@ -42,17 +55,24 @@ pub struct Worker {
 impl Worker {
     pub fn new(
         id: usize, message_tx: MessageSender, command_rx: CommandReceiver, status_tx: StatusSender,
+        lookup_tx: LookupSender, resolved_rx: ResolvedReceiver, tls: bool,
     ) -> Result<Self, WorkerError> {
         let poll = Poll::new()?;
         let events = Events::with_capacity(1024);
         Ok(Self {
             id,
+            tls,
+            resolving: Vec::new(),
+            connecting: Vec::new(),
             pending: VecDeque::new(),
             connections: Vec::new(),
             next_idx: 0,
+            last_check: Instant::now(),
             message_tx,
             command_rx,
             status_tx,
+            lookup_tx,
+            resolved_rx,
             poll,
             events,
         })
@@ -78,15 +98,106 @@ impl Worker {
 
     fn handle_command(&mut self, command: Command) {
         match command {
-            Command::Connect(config) => {
+            Command::Connect(config, limits) => {
                 tracing::info!(host = %config.hostname, cursor = ?config.cursor, "starting crawl");
-                let res = Connection::connect(&config.hostname, config.cursor);
-                self.handle_connect(Instant::now(), config.hostname, res);
+                if self.lookup_tx.send((self.id, config.hostname.clone())).is_err() {
+                    tracing::warn!(host = %config.hostname, "dns resolver gone");
+                    self.disconnected(config.hostname);
+                    return;
+                }
+                self.resolving.push((Instant::now(), config, limits));
+            }
+            Command::Disconnect(hostname) => {
+                tracing::info!(host = %hostname, "disconnecting");
+                self.resolving.retain(|(_, config, _)| config.hostname != hostname);
+                for slot in &mut self.connecting {
+                    if slot.as_ref().is_some_and(|(_, pending, ..)| *pending == hostname) {
+                        *slot = None;
+                    }
+                }
+                self.pending.retain(|(_, pending, ..)| *pending != hostname);
+                let idx = self
+                    .connections
+                    .iter()
+                    .position(|conn| conn.as_ref().is_some_and(|conn| conn.hostname == hostname));
+                let conn = idx.and_then(|idx| self.connections[idx].take());
+                if let Some(conn) = &conn {
+                    #[expect(clippy::expect_used)]
+                    self.poll
+                        .registry()
+                        .deregister(&mut SourceFd(&conn.as_raw_fd()))
+                        .expect("failed to deregister");
+                    self.report_connections();
+                }
+                // every event read from the host was sent before this
+                #[expect(clippy::expect_used)]
+                self.status_tx
+                    .push(Status::Disconnected {
+                        worker_id: self.id,
+                        hostname,
+                        connected: conn.is_some(),
+                        errored: false,
+                    })
+                    .expect("unable to send status");
+            }
+        }
+    }
+
+    fn handle_resolved(&mut self, resolved: Resolved) {
+        let mut idx = 0;
+        while idx < self.resolving.len() {
+            if self.resolving[idx].1.hostname != resolved.hostname {
+                idx += 1;
+                continue;
+            }
+            let (start, config, limits) = self.resolving.swap_remove(idx);
+            let addrs = match &resolved.addrs {
+                Ok(addrs) => addrs,
+                Err(err) => {
+                    tracing::warn!(host = %config.hostname, %err, "unable to resolve host");
+                    self.disconnected(config.hostname);
+                    continue;
+                }
+            };
+            match Connection::connect(&config.hostname, config.cursor, addrs, self.tls) {
+                Ok(connecting) => {
+                    let idx =
+                        self.connecting.iter().position(Option::is_none).unwrap_or_else(|| {
+                            let idx = self.connecting.len();
+                            self.connecting.push(None);
+                            idx
+                        });
+                    self.connecting[idx] = Some((start, config.hostname, limits, connecting));
+                    self.poll_connecting(idx);
+                }
+                Err(err) => self.handle_connect(start, config.hostname, limits, Err(err)),
             }
         }
     }
 
-    fn handle_connect(&mut self, start: Instant, hostname: String, result: HandshakeResult) {
+    fn poll_connecting(&mut self, idx: usize) {
+        let Some((start, _, _, connecting)) = &mut self.connecting[idx] else {
+            return;
+        };
+        let res = connecting.poll(self.poll.registry(), Token(CONNECTING | idx));
+        let timeout = start.elapsed() >= TIMEOUT;
+        if res.is_none() && !timeout {
+            return;
+        }
+        #[expect(clippy::unwrap_used)]
+        let (start, hostname, limits, _) = self.connecting[idx].take().unwrap();
+        match res {
+            Some(res) => self.handle_connect(start, hostname, limits, res),
+            None => {
+                tracing::warn!(host = %hostname, "requestCrawl connect timeout");
+                self.disconnected(hostname);
+            }
+        }
+    }
+
+    fn handle_connect(
+        &mut self, start: Instant, hostname: String, limits: RateLimits, result: HandshakeResult,
+    ) {
         match result {
             Ok(Ok(client)) => {
                 let idx = self.connections.iter().position(Option::is_none).unwrap_or_else(|| {
@@ -94,30 +205,49 @@ impl Worker {
                     self.connections.push(None);
                     idx
                 });
-                let conn = Connection::new(hostname, client, self.message_tx.clone());
+                let conn =
+                    Connection::new(hostname.clone(), client, self.message_tx.clone(), limits);
                 #[expect(clippy::expect_used)]
                 self.poll
                     .registry()
                     .register(&mut SourceFd(&conn.as_raw_fd()), Token(idx), INTEREST)
                     .expect("unable to register");
                 self.connections[idx] = Some(conn);
+                self.report_connections();
+                #[expect(clippy::expect_used)]
+                self.status_tx
+                    .push(Status::Connected { worker_id: self.id, hostname })
+                    .expect("unable to send status");
                 return;
             }
             Ok(Err(handshaking)) if start.elapsed() < TIMEOUT => {
-                self.pending.push_back((Instant::now(), hostname, handshaking));
+                self.pending.push_back((Instant::now(), hostname, limits, handshaking));
                 return;
             }
             Ok(Err(_)) => {
                 tracing::warn!(host = %hostname, "requestCrawl timeout");
             }
+            Err(tungstenite::Error::Http(res)) if res.status().is_redirection() => {
+                let status = res.status();
+                tracing::warn!(host = %hostname, %status, "refusing to follow redirect");
+            }
             Err(err) => {
                 tracing::warn!(host = %hostname, %err, "unable to requestCrawl");
             }
         }
 
+        self.disconnected(hostname);
+    }
+
+    fn disconnected(&mut self, hostname: String) {
         #[expect(clippy::expect_used)]
         self.status_tx
-            .push(Status::Disconnected { worker_id: self.id, hostname, connected: false })
+            .push(Status::Disconnected {
+                worker_id: self.id,
+                hostname,
+                connected: false,
+                errored: false,
+            })
             .expect("unable to send status");
     }
 
@@ -127,12 +257,38 @@ impl Worker {
         }
 
         for _ in 0..32 {
-            if let Some((start, hostname, handshaking)) = self.pending.pop_front() {
+            if let Some((start, hostname, limits, handshaking)) = self.pending.pop_front() {
                 let res = handshaking.handshake().decompose();
-                self.handle_connect(start, hostname, res);
+                self.handle_connect(start, hostname, limits, res);
+            }
+
+            while let Ok(resolved) = self.resolved_rx.pop() {
+                self.handle_resolved(resolved);
+            }
+            let now = Instant::now();
+            while let Some(idx) =
+                self.resolving.iter().position(|(start, ..)| now.duration_since(*start) >= TIMEOUT)
+            {
+                let (_, config, _) = self.resolving.swap_remove(idx);
+                tracing::warn!(host = %config.hostname, "requestCrawl dns timeout");
+                self.disconnected(config.hostname);
             }
 
-            if self.pending.len() < 16 {
+            let mut connecting = 0;
+            let mut idx = 0;
+            while idx < self.connecting.len() {
+                if let Some((start, _, _, conn)) = &self.connecting[idx] {
+                    connecting += 1;
+                    if conn.deadline().is_some_and(|deadline| deadline <= now)
+                        || now.duration_since(*start) >= TIMEOUT
+                    {
+                        self.poll_connecting(idx);
+                    }
+                }
+                idx += 1;
+            }
+
+            if self.resolving.len() + connecting + self.pending.len() < 16 {
                 if let Ok(command) = self.command_rx.pop() {
                     self.handle_command(command);
                 }
@@ -149,7 +305,12 @@ impl Worker {
                     .poll(&mut events, Some(Duration::from_millis(1)))
                     .expect("failed to poll");
                 for ev in &events {
-                    if !self.poll(ev.token().0) {
+                    let token = ev.token().0;
+                    if token & CONNECTING != 0 {
+                        self.poll_connecting(token & !CONNECTING);
+                        continue;
+                    }
+                    if !self.poll(token) {
                         break 'outer;
                     }
                 }
@@ -164,34 +325,92 @@ impl Worker {
             }
         }
 
+        if self.last_check.elapsed() > HOSTS_IDLE_CHECK {
+            self.check_idle();
+            self.last_check = Instant::now();
+        }
+
         true
     }
 
+    fn check_idle(&mut self) {
+        for idx in 0..self.connections.len() {
+            if let Some(Err(err)) = self.connections[idx].as_mut().map(Connection::keepalive) {
+                self.disconnect(idx, &err);
+            }
+        }
+
+        let elapsed = self.last_check.elapsed();
+        let mut loads = Vec::new();
+        for conn in self.connections.iter_mut().flatten() {
+            loads.push((conn.hostname.clone(), conn.take_load(elapsed)));
+            let idle = conn.last.elapsed() > HOSTS_IDLE;
+            if idle != conn.idle {
+                conn.idle = idle;
+                #[expect(clippy::expect_used)]
+                self.status_tx
+                    .push(Status::Idle {
+                        worker_id: self.id,
+                        hostname: conn.hostname.clone(),
+                        idle,
+                    })
+                    .expect("unable to send status");
+            }
+            let throttled = conn.exhausted();
+            if throttled != conn.throttled {
+                conn.throttled = throttled;
+                tracing::info!(host = %conn.hostname, %throttled, "hourly event limit");
+                #[expect(clippy::expect_used)]
+                self.status_tx
+                    .push(Status::Throttled {
+                        worker_id: self.id,
+                        hostname: conn.hostname.clone(),
+                        throttled,
+                    })
+                    .expect("unable to send status");
+            }
+        }
+        #[expect(clippy::expect_used)]
+        self.status_tx
+            .push(Status::Load { worker_id: self.id, hosts: loads })
+            .expect("unable to send status");
+    }
+
     fn poll(&mut self, idx: usize) -> bool {
         if let Some(conn) = &mut self.connections[idx] {
             match conn.poll() {
                 Ok(true) => {}
                 Ok(false) => return false,
-                Err(err) => {
-                    tracing::info!(host = %conn.hostname, %err, "disconnected");
-                    #[expect(clippy::expect_used)]
-                    self.poll
-                        .registry()
-                        .deregister(&mut SourceFd(&conn.as_raw_fd()))
-                        .expect("failed to deregister");
-                    #[expect(clippy::expect_used)]
-                    self.status_tx
-                        .push(Status::Disconnected {
-                            worker_id: self.id,
-                            hostname: conn.hostname.clone(),
-                            connected: true,
-                        })
-                        .expect("unable to send status");
-                    self.connections[idx] = None;
-                }
+                Err(err) => self.disconnect(idx, &err),
             }
         }
 
         true
     }
+
+    // tears down a live connection, the manager reconnects with backoff
+    fn disconnect(&mut self, idx: usize, err: &ConnectionError) {
+        let Some(conn) = self.connections[idx].take() else { return };
+        tracing::info!(host = %conn.hostname, %err, "disconnected");
+        #[expect(clippy::expect_used)]
+        self.poll
+            .registry()
+            .deregister(&mut SourceFd(&conn.as_raw_fd()))
+            .expect("failed to deregister");
+        self.report_connections();
+        #[expect(clippy::expect_used)]
+        self.status_tx
+            .push(Status::Disconnected {
+                worker_id: self.id,
+                hostname: conn.hostname.clone(),
+                connected: true,
+                errored: matches!(err, ConnectionError::Upstream),
+            })
+            .expect("unable to send status");
+    }
+
+    fn report_connections(&self) {
+        let connections = self.connections.iter().flatten().count();
+        Metrics::set_worker(&METRICS.crawler_connections, self.id, connections as u64);
+    }
 }
@@ -36,6 +36,7 @@
 )]
 
 mod crawler;
+mod metrics;
 mod publisher;
 mod server;
 mod types;
@@ -52,7 +53,7 @@ pub static SHUTDOWN: AtomicBool = AtomicBool::new(false);
 pub use crawler::Manager as CrawlerManager;
 pub use publisher::Manager as PublisherManager;
 pub use server::Server;
-pub use types::MessageRecycle;
+pub use types::{MessageRecycle, open_db};
 pub use validator::Manager as ValidatorManager;
 
 #[derive(Debug, Error)]
@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -20,10 +21,10 @@ use tracing_subscriber::fmt::Layer;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 
-use rsky_relay::config::{CAPACITY_MSGS, CAPACITY_REQS, WORKERS_CRAWLERS, WORKERS_PUBLISHERS};
+use rsky_relay::config::{CAPACITY_MSGS, CAPACITY_REQS, Config, ListenerConfig};
 use rsky_relay::{
     CrawlerManager, MessageRecycle, PublisherManager, RelayError, SHUTDOWN, Server,
-    ValidatorManager,
+    ValidatorManager, open_db,
 };
 
 #[global_allocator]
@@ -37,9 +38,96 @@ pub struct Args {
     certs: Option<PathBuf>,
     #[clap(short, long, requires = "certs")]
     private_key: Option<PathBuf>,
+    /// Bearer token for the /admin endpoints, which are disabled when unset
+    #[clap(long, env = "RSKY_RELAY_ADMIN_PASSWORD")]
+    admin_password: Option<String>,
+    /// TOML config file, see the README for the available keys
+    #[clap(long, env = "RSKY_RELAY_CONFIG")]
+    config: Option<PathBuf>,
+    #[clap(long, env = "RSKY_RELAY_PORT")]
+    port: Option<u16>,
+    /// Plaintext listener, eg `127.0.0.1:9000` or `[::]:9000` (repeatable)
+    #[clap(long)]
+    listen: Vec<SocketAddr>,
+    /// TLS listener, requires --certs and --private-key (repeatable)
+    #[clap(long)]
+    listen_tls: Vec<SocketAddr>,
+    /// Plaintext listener that also serves the /admin routes (repeatable)
+    #[clap(long)]
+    listen_admin: Vec<SocketAddr>,
+    /// Relay queried for hosts to crawl
+    #[cfg(not(feature = "labeler"))]
+    #[clap(long, env = "RSKY_RELAY_HOSTS_RELAY")]
+    hosts_relay: Option<String>,
+    #[clap(long, env = "RSKY_RELAY_WORKERS_CRAWLERS")]
+    workers_crawlers: Option<usize>,
+    #[clap(long, env = "RSKY_RELAY_WORKERS_PUBLISHERS")]
+    workers_publishers: Option<usize>,
+    #[clap(long, env = "RSKY_RELAY_RELAY_DB")]
+    relay_db: Option<PathBuf>,
+    #[clap(long, env = "RSKY_RELAY_PLC_DIRECTORY_DB")]
+    plc_directory_db: Option<PathBuf>,
+    /// Directory of the fjall database holding the firehose
+    #[clap(long, env = "RSKY_RELAY_DB_PATH")]
+    db_path: Option<PathBuf>,
     #[cfg(not(feature = "labeler"))]
     #[clap(long)]
     no_plc_export: bool,
+    /// Reject commits whose ops can't be inverted back to the previous repo state
+    #[cfg(not(feature = "labeler"))]
+    #[clap(long)]
+    strict_mst: bool,
+    /// Crawl hosts over plain ws://, with ports and private addresses allowed
+    #[clap(long)]
+    dev_mode: bool,
+}
+
+impl Args {
+    fn apply(self, config: &mut Config) {
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if !(self.listen.is_empty() && self.listen_tls.is_empty() && self.listen_admin.is_empty()) {
+            let listener = |tls, admin| move |address| ListenerConfig { address, tls, admin };
+            config.listeners = self
+                .listen
+                .into_iter()
+                .map(listener(false, false))
+                .chain(self.listen_tls.into_iter().map(listener(true, false)))
+                .chain(self.listen_admin.into_iter().map(listener(false, true)))
+                .collect();
+        }
+        #[cfg(not(feature = "labeler"))]
+        if let Some(hosts_relay) = self.hosts_relay {
+            config.hosts_relay = hosts_relay;
+        }
+        if let Some(workers_crawlers) = self.workers_crawlers {
+            config.workers_crawlers = workers_crawlers;
+        }
+        if let Some(workers_publishers) = self.workers_publishers {
+            config.workers_publishers = workers_publishers;
+        }
+        if let Some(relay_db) = self.relay_db {
+            config.relay_db = relay_db;
+        }
+        if let Some(plc_directory_db) = self.plc_directory_db {
+            config.plc_directory_db = plc_directory_db;
+        }
+        if let Some(db_path) = self.db_path {
+            config.db_path = db_path;
+        }
+        #[cfg(not(feature = "labeler"))]
+        if self.no_plc_export {
+            config.plc_export = false;
+        }
+        #[cfg(not(feature = "labeler"))]
+        if self.strict_mst {
+            config.strict_mst = true;
+        }
+        if self.dev_mode {
+            config.dev_mode = true;
+        }
+    }
 }
 
 #[tokio::main]
@@ -63,7 +151,12 @@ pub async fn main() -> Result<()> {
     #[expect(clippy::unwrap_used)]
     default_provider().install_default().unwrap();
 
-    let args = Args::parse();
+    let mut args = Args::parse();
+    let mut config = Config::load(args.config.as_deref())?;
+    let ssl_configs = args.certs.take().zip(args.private_key.take());
+    let admin_password = args.admin_password.take();
+    args.apply(&mut config);
+    config.validate()?;
 
     let terminate_now = Arc::new(AtomicBool::new(false));
     flag::register_conditional_shutdown(SIGINT, 1, Arc::clone(&terminate_now))?;
@@ -72,13 +165,25 @@ pub async fn main() -> Result<()> {
     let (message_tx, message_rx) =
         thingbuf::mpsc::blocking::with_recycle(CAPACITY_MSGS, MessageRecycle);
     let (request_crawl_tx, request_crawl_rx) = rtrb::RingBuffer::new(CAPACITY_REQS);
+    let (admin_tx, admin_rx) = rtrb::RingBuffer::new(CAPACITY_REQS);
+    let (account_tx, account_rx) = rtrb::RingBuffer::new(CAPACITY_REQS);
     let (subscribe_repos_tx, subscribe_repos_rx) = rtrb::RingBuffer::new(CAPACITY_REQS);
-    let server =
-        Server::new(args.certs.zip(args.private_key), request_crawl_tx, subscribe_repos_tx)?;
-    let validator = ValidatorManager::new(message_rx)?;
+    let (cursor_tx, cursor_rx) = rtrb::RingBuffer::new(CAPACITY_REQS);
+    let db = open_db(&config)?;
+    // the validator creates relay.db, which the server opens read-only
+    let validator = ValidatorManager::new(&config, db.clone(), message_rx, account_rx, cursor_tx)?;
+    let server = Server::new(
+        &config,
+        ssl_configs,
+        admin_password,
+        request_crawl_tx,
+        admin_tx,
+        account_tx,
+        subscribe_repos_tx,
+    )?;
     let handle = tokio::spawn(validator.run());
-    let crawler = CrawlerManager::new(WORKERS_CRAWLERS, &message_tx, request_crawl_rx)?;
-    let publisher = PublisherManager::new(WORKERS_PUBLISHERS, subscribe_repos_rx)?;
+    let crawler = CrawlerManager::new(&config, &message_tx, request_crawl_rx, admin_rx, cursor_rx)?;
+    let publisher = PublisherManager::new(&config, &db, subscribe_repos_rx)?;
     #[expect(clippy::vec_init_then_push)]
     let ret = thread::scope(move |s| {
         let mut handles = Vec::<ScopedJoinHandle<'_, Result<_, RelayError>>>::new();
@@ -0,0 +1,133 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Upper bound on the number of crawler/publisher workers that get their own series.
+pub const MAX_WORKERS: usize = 64;
+
+const EVENT_TYPES: [&str; 6] = ["#commit", "#sync", "#identity", "#account", "#labels", "unknown"];
+
+pub static METRICS: Metrics = Metrics::new();
+
+/// Process-wide counters and gauges, rendered in the Prometheus text format by `GET /metrics`.
+#[derive(Debug)]
+pub struct Metrics {
+    // crawler
+    pub crawler_connections: [AtomicU64; MAX_WORKERS],
+    pub messages_capacity: AtomicU64,
+    pub messages_remaining: AtomicU64,
+    // validator
+    events_accepted: [AtomicU64; EVENT_TYPES.len()],
+    events_rejected: [AtomicU64; EVENT_TYPES.len()],
+    pub resolver_cache_hits: AtomicU64,
+    pub resolver_cache_misses: AtomicU64,
+    pub resolver_inflight: AtomicU64,
+    pub queue_len: AtomicU64,
+    pub firehose_seq: AtomicU64,
+    // publisher
+    pub publisher_subscribers: [AtomicU64; MAX_WORKERS],
+    pub publisher_lag: [AtomicU64; MAX_WORKERS],
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            crawler_connections: [const { AtomicU64::new(0) }; MAX_WORKERS],
+            messages_capacity: AtomicU64::new(0),
+            messages_remaining: AtomicU64::new(0),
+            events_accepted: [const { AtomicU64::new(0) }; EVENT_TYPES.len()],
+            events_rejected: [const { AtomicU64::new(0) }; EVENT_TYPES.len()],
+            resolver_cache_hits: AtomicU64::new(0),
+            resolver_cache_misses: AtomicU64::new(0),
+            resolver_inflight: AtomicU64::new(0),
+            queue_len: AtomicU64::new(0),
+            firehose_seq: AtomicU64::new(0),
+            publisher_subscribers: [const { AtomicU64::new(0) }; MAX_WORKERS],
+            publisher_lag: [const { AtomicU64::new(0) }; MAX_WORKERS],
+        }
+    }
+
+    #[inline]
+    pub fn accepted(&self, type_: &str) {
+        self.events_accepted[event_index(type_)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn rejected(&self, type_: &str) {
+        self.events_rejected[event_index(type_)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn set_worker(gauges: &[AtomicU64; MAX_WORKERS], worker_id: usize, value: u64) {
+        if let Some(gauge) = gauges.get(worker_id) {
+            gauge.store(value, Ordering::Relaxed);
+        }
+    }
+
+    pub fn render(&self, n_crawlers: usize, n_publishers: usize) -> String {
+        let mut out = String::with_capacity(4096);
+        let n_crawlers = n_crawlers.min(MAX_WORKERS);
+        let n_publishers = n_publishers.min(MAX_WORKERS);
+
+        header(&mut out, "relay_crawler_connections", "gauge", "Connected upstream hosts");
+        for (id, gauge) in self.crawler_connections[..n_crawlers].iter().enumerate() {
+            sample(&mut out, "relay_crawler_connections", "worker", id, gauge);
+        }
+        header(&mut out, "relay_messages_capacity", "gauge", "Crawler to validator channel size");
+        single(&mut out, "relay_messages_capacity", &self.messages_capacity);
+        header(&mut out, "relay_messages_remaining", "gauge", "Free slots in the message channel");
+        single(&mut out, "relay_messages_remaining", &self.messages_remaining);
+
+        header(&mut out, "relay_events_accepted_total", "counter", "Events added to the firehose");
+        for (type_, counter) in EVENT_TYPES.iter().zip(&self.events_accepted) {
+            sample(&mut out, "relay_events_accepted_total", "type", type_, counter);
+        }
+        header(&mut out, "relay_events_rejected_total", "counter", "Events rejected by validation");
+        for (type_, counter) in EVENT_TYPES.iter().zip(&self.events_rejected) {
+            sample(&mut out, "relay_events_rejected_total", "type", type_, counter);
+        }
+        header(&mut out, "relay_resolver_cache_hits_total", "counter", "Identity cache hits");
+        single(&mut out, "relay_resolver_cache_hits_total", &self.resolver_cache_hits);
+        header(&mut out, "relay_resolver_cache_misses_total", "counter", "Identity cache misses");
+        single(&mut out, "relay_resolver_cache_misses_total", &self.resolver_cache_misses);
+        header(&mut out, "relay_resolver_inflight", "gauge", "Identity lookups in flight");
+        single(&mut out, "relay_resolver_inflight", &self.resolver_inflight);
+        header(&mut out, "relay_queue_len", "gauge", "Events waiting on identity resolution");
+        single(&mut out, "relay_queue_len", &self.queue_len);
+        header(&mut out, "relay_firehose_seq", "gauge", "Sequence number of the firehose head");
+        single(&mut out, "relay_firehose_seq", &self.firehose_seq);
+
+        header(&mut out, "relay_publisher_subscribers", "gauge", "Connected downstream consumers");
+        for (id, gauge) in self.publisher_subscribers[..n_publishers].iter().enumerate() {
+            sample(&mut out, "relay_publisher_subscribers", "worker", id, gauge);
+        }
+        header(
+            &mut out,
+            "relay_publisher_lag",
+            "gauge",
+            "Events behind head of the slowest consumer",
+        );
+        for (id, gauge) in self.publisher_lag[..n_publishers].iter().enumerate() {
+            sample(&mut out, "relay_publisher_lag", "worker", id, gauge);
+        }
+
+        out
+    }
+}
+
+fn event_index(type_: &str) -> usize {
+    EVENT_TYPES.iter().position(|t| *t == type_).unwrap_or(EVENT_TYPES.len() - 1)
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _err = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}");
+}
+
+fn single(out: &mut String, name: &str, value: &AtomicU64) {
+    let _err = writeln!(out, "{name} {}", value.load(Ordering::Relaxed));
+}
+
+fn sample(
+    out: &mut String, name: &str, label: &str, value: impl std::fmt::Display, metric: &AtomicU64,
+) {
+    let _err = writeln!(out, "{name}{{{label}=\"{value}\"}} {}", metric.load(Ordering::Relaxed));
+}
@@ -2,10 +2,11 @@ use std::sync::atomic::Ordering;
 use std::time::Duration;
 use std::{io, thread};
 
+use fjall::Keyspace;
 use thiserror::Error;
 
 use crate::SHUTDOWN;
-use crate::config::CAPACITY_STATUS;
+use crate::config::{CAPACITY_STATUS, Config};
 use crate::publisher::types::{Command, CommandSender, SubscribeReposReceiver};
 use crate::publisher::worker::{Worker, WorkerError};
 
@@ -43,14 +44,15 @@ pub struct Manager {
 
 impl Manager {
     pub fn new(
-        n_workers: usize, subscribe_repos_rx: SubscribeReposReceiver,
+        config: &Config, db: &Keyspace, subscribe_repos_rx: SubscribeReposReceiver,
     ) -> Result<Self, ManagerError> {
-        let workers = (0..n_workers)
+        let workers = (0..config.workers_publishers)
             .map(|worker_id| -> Result<_, ManagerError> {
+                let db = db.clone();
                 let (command_tx, command_rx) = rtrb::RingBuffer::new(CAPACITY_STATUS);
                 let thread_handle = thread::Builder::new()
                     .name(format!("rsky-pub-{worker_id}"))
-                    .spawn(move || Worker::new(worker_id, command_rx)?.run())?;
+                    .spawn(move || Worker::new(worker_id, &db, command_rx)?.run())?;
                 Ok(WorkerHandle { command_tx, thread_handle })
             })
             .collect::<Result<Vec<_>, _>>()?;
@@ -4,15 +4,16 @@ use std::time::Duration;
 use std::{io, thread};
 
 use bytes::Bytes;
-use fjall::{PartitionCreateOptions, PartitionHandle};
+use fjall::{Keyspace, PartitionCreateOptions, PartitionHandle};
 use mio::unix::SourceFd;
 use mio::{Events, Interest, Poll, Token};
 use thiserror::Error;
 
 use crate::SHUTDOWN;
+use crate::metrics::{METRICS, Metrics};
 use crate::publisher::connection::{Connection, ConnectionError};
 use crate::publisher::types::{Command, CommandReceiver};
-use crate::types::{Cursor, DB};
+use crate::types::Cursor;
 
 const INTEREST: Interest = Interest::WRITABLE;
 
@@ -37,8 +38,8 @@ pub struct Worker {
 }
 
 impl Worker {
-    pub fn new(id: usize, command_rx: CommandReceiver) -> Result<Self, WorkerError> {
-        let firehose = DB.open_partition("firehose", PartitionCreateOptions::default())?;
+    pub fn new(id: usize, db: &Keyspace, command_rx: CommandReceiver) -> Result<Self, WorkerError> {
+        let firehose = db.open_partition("firehose", PartitionCreateOptions::default())?;
         let poll = Poll::new()?;
         let events = Events::with_capacity(1024);
         Ok(Self { id, connections: Vec::new(), next_idx: 0, command_rx, firehose, poll, events })
@@ -132,6 +133,13 @@ impl Worker {
             }
         }
 
+        let (subscribers, lag) =
+            self.connections.iter().flatten().fold((0, 0), |(n, lag), conn| {
+                (n + 1, lag.max(seq.get().saturating_sub(conn.cursor.get())))
+            });
+        Metrics::set_worker(&METRICS.publisher_subscribers, self.id, subscribers);
+        Metrics::set_worker(&METRICS.publisher_lag, self.id, lag);
+
         Ok(true)
     }
 
@@ -1,5 +1,7 @@
 use std::fs::File;
 use std::io::{self, BufReader, Write};
+#[cfg(not(feature = "labeler"))]
+use std::net::ToSocketAddrs;
 use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -10,26 +12,41 @@ use std::time::{Duration, Instant};
 
 use color_eyre::Result;
 use color_eyre::eyre::eyre;
-use httparse::{EMPTY_HEADER, Status};
-#[cfg(feature = "labeler")]
-use rusqlite::{Connection, OpenFlags};
+use httparse::{EMPTY_HEADER, Header, Status};
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
 use rustls::{ServerConfig, ServerConnection, StreamOwned};
+#[cfg(not(feature = "labeler"))]
+use serde::Serialize;
 use thiserror::Error;
 use url::Url;
 
 use crate::SHUTDOWN;
-use crate::config::{HOSTS_INTERVAL, PORT};
+use crate::config::{Config, HOSTS_INTERVAL, ListenerConfig};
+#[cfg(not(feature = "labeler"))]
+use crate::config::{
+    HOSTS_MIN_ACCOUNTS, HOSTS_VERIFY_MAX, HOSTS_VERIFY_TIMEOUT, LIST_HOSTS_LIMIT,
+    LIST_HOSTS_LIMIT_MAX,
+};
 #[cfg(not(feature = "labeler"))]
-use crate::config::{HOSTS_MIN_ACCOUNTS, HOSTS_RELAY};
-use crate::crawler::{RequestCrawl, RequestCrawlSender};
+use crate::crawler::is_public;
+use crate::crawler::{
+    AdminCommand, AdminSender, HostnameError, RequestCrawl, RequestCrawlSender, split_port,
+    validate_hostname,
+};
+use crate::metrics::METRICS;
 use crate::publisher::{MaybeTlsStream, SubscribeRepos, SubscribeReposSender};
+use crate::server::types::{AdminAccount, AdminHost, HostRejections, Rejection, XrpcError};
 #[cfg(not(feature = "labeler"))]
-use crate::server::types::{HostStatus, ListHosts};
+use crate::server::types::{DescribeServer, Host, ListHosts};
+use crate::types::{HostStatus, open_relay_db};
+use crate::validator::{AccountCommand, AccountSender, AccountStatus};
 
 const SLEEP: Duration = Duration::from_millis(10);
 
 #[cfg(not(feature = "labeler"))]
 const PATH_LIST_HOSTS: &str = "/xrpc/com.atproto.sync.listHosts";
+#[cfg(not(feature = "labeler"))]
+const PATH_GET_HOST_STATUS: &str = "/xrpc/com.atproto.sync.getHostStatus";
 
 const PATH_SUBSCRIBE: &str = if cfg!(feature = "labeler") {
     "/xrpc/com.atproto.label.subscribeLabels"
@@ -42,6 +59,16 @@ const PATH_REQUEST_CRAWL: &str = if cfg!(feature = "labeler") {
     "/xrpc/com.atproto.sync.requestCrawl"
 };
 
+const PATH_METRICS: &str = "/metrics";
+
+const PATH_ADMIN_BAN: &str = "/admin/hosts/ban";
+const PATH_ADMIN_UNBAN: &str = "/admin/hosts/unban";
+const PATH_ADMIN_DISCONNECT: &str = "/admin/hosts/disconnect";
+const PATH_ADMIN_REJECTIONS: &str = "/admin/hosts/rejections";
+const PATH_ADMIN_TAKEDOWN: &str = "/admin/accounts/takedown";
+const PATH_ADMIN_SUSPEND: &str = "/admin/accounts/suspend";
+const PATH_ADMIN_RESTORE: &str = "/admin/accounts/restore";
+
 const INDEX_ASCII: &str = r"
     .------..------..------..------.
     |R.--. ||S.--. ||K.--. ||Y.--. |
@@ -72,9 +99,10 @@ pub enum ServerError {
     PushError(#[from] rtrb::PushError<RequestCrawl>),
     #[error("url parse error: {0}")]
     UrlParse(#[from] url::ParseError),
-    #[cfg(feature = "labeler")]
     #[error("sqlite error: {0}")]
     Sqlite(#[from] rusqlite::Error),
+    #[error("tls listener {0} requires --certs and --private-key")]
+    MissingCerts(SocketAddr),
 }
 
 #[derive(Debug)]
@@ -86,28 +114,59 @@ impl Drop for ErrorOnDropTcpStream {
         let Some(mut stream) = self.0.take() else {
             return;
         };
-        let _err = stream.write_all(b"HTTP/1.1 400 Bad Request\n");
+        let body = br#"{"error":"InvalidRequest","message":"invalid request"}"#;
+        let _err = write!(
+            stream,
+            "HTTP/1.1 400 Bad Request\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\
+             \r\n",
+            body.len()
+        );
+        let _err = stream.write_all(body);
         let _err = stream.flush();
         let _err = stream.shutdown();
     }
 }
 
+#[derive(Debug)]
+struct Listener {
+    inner: TcpListener,
+    tls: bool,
+    admin: bool,
+}
+
 #[derive(Debug)]
 pub struct Server {
-    listener: TcpListener,
+    listeners: Vec<Listener>,
     tls_config: Option<Arc<ServerConfig>>,
     base_url: Url,
     buf: Vec<u8>,
     last: Instant,
+    #[cfg(not(feature = "labeler"))]
+    hosts_relay: String,
+    hosts_allowlist: Vec<String>,
+    dev_mode: bool,
+    // new hosts being checked before their first requestCrawl is accepted
+    #[cfg(not(feature = "labeler"))]
+    verifying: Vec<(String, thread::JoinHandle<Option<RequestCrawl>>)>,
+    workers_crawlers: usize,
+    workers_publishers: usize,
     #[cfg(feature = "labeler")]
     conn: Connection,
+    relay_conn: Connection,
+    admin_password: Option<String>,
     request_crawl_tx: RequestCrawlSender,
+    admin_tx: AdminSender,
+    account_tx: AccountSender,
     subscribe_repos_tx: SubscribeReposSender,
 }
 
 impl Server {
     pub fn new(
-        ssl_configs: Option<(PathBuf, PathBuf)>, request_crawl_tx: RequestCrawlSender,
+        config: &Config, ssl_configs: Option<(PathBuf, PathBuf)>, admin_password: Option<String>,
+        request_crawl_tx: RequestCrawlSender, admin_tx: AdminSender, account_tx: AccountSender,
         subscribe_repos_tx: SubscribeReposSender,
     ) -> Result<Self, ServerError> {
         let tls_config = if let Some((certs, private_key)) = ssl_configs {
@@ -125,25 +184,49 @@ impl Server {
             None
         };
 
-        let listener = TcpListener::bind(format!("0.0.0.0:{PORT}"))?;
-        listener.set_nonblocking(true)?;
+        let mut listeners = Vec::new();
+        for ListenerConfig { address, tls, admin } in config.listeners(tls_config.is_some()) {
+            if tls && tls_config.is_none() {
+                return Err(ServerError::MissingCerts(address));
+            }
+            let inner = TcpListener::bind(address)?;
+            inner.set_nonblocking(true)?;
+            tracing::info!(%address, %tls, %admin, "listening");
+            listeners.push(Listener { inner, tls, admin });
+        }
+        if admin_password.is_some() && !listeners.iter().any(|listener| listener.admin) {
+            tracing::warn!("admin password set without an admin listener, see --listen-admin");
+        }
         let base_url = Url::parse("http://example.com")?;
         let now = Instant::now();
         let last = now.checked_sub(HOSTS_INTERVAL).unwrap_or(now);
         #[cfg(feature = "labeler")]
         let conn = Connection::open_with_flags(
-            "plc_directory.db",
+            &config.plc_directory_db,
             OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
         )?;
+        let relay_conn = open_relay_db(config, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
         Ok(Self {
-            listener,
+            listeners,
             tls_config,
             base_url,
             buf: vec![0; 1024],
             last,
+            #[cfg(not(feature = "labeler"))]
+            hosts_relay: config.hosts_relay.clone(),
+            hosts_allowlist: config.hosts_allowlist.clone(),
+            dev_mode: config.dev_mode,
+            #[cfg(not(feature = "labeler"))]
+            verifying: Vec::new(),
+            workers_crawlers: config.workers_crawlers,
+            workers_publishers: config.workers_publishers,
             #[cfg(feature = "labeler")]
             conn,
+            relay_conn,
+            admin_password,
             request_crawl_tx,
+            admin_tx,
+            account_tx,
             subscribe_repos_tx,
         })
     }
@@ -168,33 +251,47 @@ impl Server {
             self.last = Instant::now();
         }
 
-        match self.listener.accept() {
-            Ok((mut stream, addr)) => {
-                tracing::trace!(%addr, "received request");
-                let stream = if let Some(tls_config) = self.tls_config.clone() {
-                    let mut conn = ServerConnection::new(tls_config)?;
+        #[cfg(not(feature = "labeler"))]
+        while let Some(idx) = self.verifying.iter().position(|(_, handle)| handle.is_finished()) {
+            let (_, handle) = self.verifying.swap_remove(idx);
+            if let Ok(Some(request_crawl)) = handle.join() {
+                self.request_crawl_tx.push(request_crawl)?;
+            }
+        }
+
+        let mut accepted = Vec::new();
+        for listener in &self.listeners {
+            match listener.inner.accept() {
+                Ok((stream, addr)) => accepted.push((stream, addr, listener.tls, listener.admin)),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => Err(e)?,
+            }
+        }
+
+        for (mut stream, addr, tls, admin) in accepted {
+            tracing::trace!(%addr, "received request");
+            let stream = match &self.tls_config {
+                Some(tls_config) if tls => {
+                    let mut conn = ServerConnection::new(Arc::clone(tls_config))?;
                     if let Err(err) = conn.complete_io(&mut stream) {
                         tracing::info!(%addr, %err, "tls handshake error");
                     }
                     let stream = StreamOwned::new(conn, stream);
                     MaybeTlsStream::Rustls(stream)
-                } else {
-                    MaybeTlsStream::Plain(stream)
-                };
-                if let Err(err) = self.handle_stream(ErrorOnDropTcpStream(Some(stream)), addr) {
-                    tracing::info!(%addr, %err, "invalid request");
                 }
+                _ => MaybeTlsStream::Plain(stream),
+            };
+            if let Err(err) = self.handle_stream(ErrorOnDropTcpStream(Some(stream)), addr, admin) {
+                tracing::info!(%addr, %err, "invalid request");
             }
-            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                return Ok(true);
-            }
-            Err(e) => Err(e)?,
         }
 
         Ok(true)
     }
 
-    fn handle_stream(&mut self, mut stream: ErrorOnDropTcpStream, addr: SocketAddr) -> Result<()> {
+    fn handle_stream(
+        &mut self, mut stream: ErrorOnDropTcpStream, addr: SocketAddr, admin: bool,
+    ) -> Result<()> {
         // only peek to allow tungstenite to complete the handshake
         #[expect(clippy::unwrap_used)]
         let len = stream.0.as_mut().unwrap().peek(&mut self.buf)?;
@@ -207,24 +304,61 @@ impl Server {
         let url = Url::options().base_url(Some(&self.base_url)).parse(path)?;
         match (method, url.path()) {
             ("GET", "/") => {
-                let body = INDEX_ASCII;
-                let response = format!(
-                    "HTTP/1.1 200 OK\r\n\
-                     Content-Type: text/plain; charset=utf-8\r\n\
-                     Content-Length: {}\r\n\
-                     Connection: close\r\n\
-                     \r\n\
-                     {}",
-                    body.len(),
-                    body
-                );
-
-                #[expect(clippy::unwrap_used)]
-                let mut stream = stream.0.take().unwrap();
-                stream.write_all(response.as_bytes())?;
-                stream.flush()?;
-                stream.shutdown()?;
-                Ok(())
+                respond(stream, "200 OK", "text/plain; charset=utf-8", INDEX_ASCII.as_bytes())
+            }
+            ("GET", PATH_METRICS) if admin => {
+                if !self.is_admin(parser.headers) {
+                    return respond_error(
+                        stream,
+                        "401 Unauthorized",
+                        "AuthRequired",
+                        "invalid admin credentials",
+                    );
+                }
+                let body = METRICS.render(self.workers_crawlers, self.workers_publishers);
+                respond(stream, "200 OK", "text/plain; version=0.0.4", body.as_bytes())
+            }
+            #[cfg(not(feature = "labeler"))]
+            ("GET", PATH_LIST_HOSTS) => {
+                let mut limit = LIST_HOSTS_LIMIT;
+                let mut cursor = String::new();
+                for (key, value) in url.query_pairs() {
+                    match &*key {
+                        "limit" => {
+                            let Ok(value) = u16::from_str(&value) else {
+                                return respond_error(
+                                    stream,
+                                    "400 Bad Request",
+                                    "InvalidRequest",
+                                    "limit must be an integer",
+                                );
+                            };
+                            limit = value.clamp(1, LIST_HOSTS_LIMIT_MAX);
+                        }
+                        "cursor" => cursor = value.into_owned(),
+                        _ => {}
+                    }
+                }
+                let hosts = self.list_hosts(&cursor, limit)?;
+                respond_json(stream, &hosts)
+            }
+            #[cfg(not(feature = "labeler"))]
+            ("GET", PATH_GET_HOST_STATUS) => {
+                let Some((_, hostname)) = url.query_pairs().find(|(key, _)| key == "hostname")
+                else {
+                    return respond_error(
+                        stream,
+                        "400 Bad Request",
+                        "InvalidRequest",
+                        "hostname is required",
+                    );
+                };
+                match self.get_host_status(&hostname)? {
+                    Some(host) => respond_json(stream, &host),
+                    None => {
+                        respond_error(stream, "400 Bad Request", "HostNotFound", "host not found")
+                    }
+                }
             }
             ("GET", PATH_SUBSCRIBE) => {
                 let mut cursor = None;
@@ -242,26 +376,252 @@ impl Server {
                 Ok(())
             }
             ("POST", PATH_REQUEST_CRAWL) => {
-                if let Status::Complete(offset) = res {
-                    if let Ok(request_crawl) =
-                        serde_json::from_reader::<_, RequestCrawl>(&self.buf[offset..len])
-                    {
-                        self.request_crawl_tx.push(request_crawl)?;
-                        #[expect(clippy::unwrap_used)]
-                        let mut stream = stream.0.take().unwrap();
-                        stream.write_all(b"HTTP/1.1 200 OK\n")?;
-                        stream.flush()?;
-                        stream.shutdown()?;
-                        return Ok(());
+                let request_crawl = match res {
+                    Status::Complete(offset) => {
+                        serde_json::from_slice::<RequestCrawl>(&self.buf[offset..len]).ok()
                     }
+                    Status::Partial => None,
+                };
+                let Some(mut request_crawl) = request_crawl else {
+                    return respond_error(
+                        stream,
+                        "400 Bad Request",
+                        "InvalidRequest",
+                        "hostname is required",
+                    );
+                };
+                request_crawl.hostname.make_ascii_lowercase();
+                if let Err(err) = self.check_hostname(&request_crawl.hostname) {
+                    return respond_error(
+                        stream,
+                        "400 Bad Request",
+                        "InvalidRequest",
+                        &format!("invalid hostname: {err}"),
+                    );
                 }
-
-                Err(eyre!("unknown hostname"))
+                match self.host_status(&request_crawl.hostname)? {
+                    Some(HostStatus::Banned) => {
+                        return respond_error(
+                            stream,
+                            "403 Forbidden",
+                            "HostBanned",
+                            "host is banned",
+                        );
+                    }
+                    #[cfg(not(feature = "labeler"))]
+                    None if !self.dev_mode && !self.is_allowlisted(&request_crawl.hostname) => {
+                        return self.verify(stream, request_crawl);
+                    }
+                    _ => {}
+                }
+                self.request_crawl_tx.push(request_crawl)?;
+                respond(stream, "200 OK", "application/json", b"{}")
+            }
+            ("POST", PATH_ADMIN_BAN | PATH_ADMIN_UNBAN | PATH_ADMIN_DISCONNECT) if admin => {
+                if !self.is_admin(parser.headers) {
+                    return respond_error(
+                        stream,
+                        "401 Unauthorized",
+                        "AuthRequired",
+                        "invalid admin credentials",
+                    );
+                }
+                let Status::Complete(offset) = res else {
+                    return Err(eyre!("partial request"));
+                };
+                let Ok(AdminHost { hostname }) = serde_json::from_slice(&self.buf[offset..len])
+                else {
+                    return respond_error(
+                        stream,
+                        "400 Bad Request",
+                        "InvalidRequest",
+                        "hostname is required",
+                    );
+                };
+                let command = match url.path() {
+                    PATH_ADMIN_BAN => AdminCommand::Ban(hostname),
+                    PATH_ADMIN_UNBAN => AdminCommand::Unban(hostname),
+                    _ => AdminCommand::Disconnect(hostname),
+                };
+                tracing::info!(%addr, ?command, "admin request");
+                self.admin_tx.push(command)?;
+                respond(stream, "200 OK", "application/json", b"{}")
+            }
+            ("POST", PATH_ADMIN_TAKEDOWN | PATH_ADMIN_SUSPEND | PATH_ADMIN_RESTORE) if admin => {
+                if !self.is_admin(parser.headers) {
+                    return respond_error(
+                        stream,
+                        "401 Unauthorized",
+                        "AuthRequired",
+                        "invalid admin credentials",
+                    );
+                }
+                let Status::Complete(offset) = res else {
+                    return Err(eyre!("partial request"));
+                };
+                let Some(AdminAccount { did }) =
+                    serde_json::from_slice::<AdminAccount>(&self.buf[offset..len])
+                        .ok()
+                        .filter(|account| account.did.starts_with("did:"))
+                else {
+                    return respond_error(
+                        stream,
+                        "400 Bad Request",
+                        "InvalidRequest",
+                        "did is required",
+                    );
+                };
+                let command = match url.path() {
+                    PATH_ADMIN_TAKEDOWN => {
+                        AccountCommand::Takedown { did, status: AccountStatus::Takendown }
+                    }
+                    PATH_ADMIN_SUSPEND => {
+                        AccountCommand::Takedown { did, status: AccountStatus::Suspended }
+                    }
+                    _ => AccountCommand::Restore { did },
+                };
+                tracing::info!(%addr, ?command, "admin request");
+                self.account_tx.push(command)?;
+                respond(stream, "200 OK", "application/json", b"{}")
+            }
+            ("GET", PATH_ADMIN_REJECTIONS) if admin => {
+                if !self.is_admin(parser.headers) {
+                    return respond_error(
+                        stream,
+                        "401 Unauthorized",
+                        "AuthRequired",
+                        "invalid admin credentials",
+                    );
+                }
+                let Some((_, hostname)) = url.query_pairs().find(|(key, _)| key == "hostname")
+                else {
+                    return respond_error(
+                        stream,
+                        "400 Bad Request",
+                        "InvalidRequest",
+                        "hostname is required",
+                    );
+                };
+                let rejections = self.host_rejections(&hostname)?;
+                respond_json(stream, &rejections)
             }
             _ => Err(eyre!("unknown request")),
         }
     }
 
+    fn is_admin(&self, headers: &[Header<'_>]) -> bool {
+        let Some(password) = &self.admin_password else {
+            return false;
+        };
+        headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case("authorization"))
+            .and_then(|header| header.value.strip_prefix(b"Bearer "))
+            .is_some_and(|token| constant_time_eq(token, password.as_bytes()))
+    }
+
+    fn is_allowlisted(&self, hostname: &str) -> bool {
+        self.hosts_allowlist.iter().any(|allowed| allowed == hostname)
+    }
+
+    fn check_hostname(&self, hostname: &str) -> Result<(), HostnameError> {
+        if self.is_allowlisted(hostname) {
+            return Ok(());
+        }
+        if self.dev_mode {
+            // any host and port, eg `localhost:2583`
+            return split_port(hostname).map(drop);
+        }
+        validate_hostname(hostname)
+    }
+
+    // checks the host is a PDS on a separate thread, which answers the request
+    #[cfg(not(feature = "labeler"))]
+    fn verify(&mut self, stream: ErrorOnDropTcpStream, request_crawl: RequestCrawl) -> Result<()> {
+        // the first request gets the outcome, this one hasn't passed anything yet
+        if self.verifying.iter().any(|(hostname, _)| *hostname == request_crawl.hostname) {
+            return respond_error(
+                stream,
+                "429 Too Many Requests",
+                "RateLimitExceeded",
+                "host verification already in progress, try again later",
+            );
+        }
+        if self.verifying.len() >= HOSTS_VERIFY_MAX {
+            return respond_error(
+                stream,
+                "429 Too Many Requests",
+                "RateLimitExceeded",
+                "too many hosts being verified, try again later",
+            );
+        }
+        let hostname = request_crawl.hostname.clone();
+        let handle = thread::Builder::new().name("rsky-verify".into()).spawn(move || {
+            match verify_host(&request_crawl.hostname) {
+                Ok(()) => {
+                    tracing::info!(host = %request_crawl.hostname, "verified new host");
+                    let _err = respond(stream, "200 OK", "application/json", b"{}");
+                    Some(request_crawl)
+                }
+                Err(err) => {
+                    tracing::info!(host = %request_crawl.hostname, %err, "host verification failed");
+                    let message = format!("host is not a PDS: {err}");
+                    let _err = respond_error(stream, "400 Bad Request", "InvalidRequest", &message);
+                    None
+                }
+            }
+        })?;
+        self.verifying.push((hostname, handle));
+        Ok(())
+    }
+
+    fn host_status(&self, hostname: &str) -> Result<Option<HostStatus>> {
+        let mut stmt =
+            self.relay_conn.prepare_cached("SELECT status FROM hosts WHERE host = ?1")?;
+        Ok(stmt.query_one((hostname,), |row| row.get::<_, HostStatus>("status")).optional()?)
+    }
+
+    // counts are written by the validator every few seconds
+    fn host_rejections(&self, hostname: &str) -> Result<HostRejections> {
+        let mut stmt = self.relay_conn.prepare_cached(
+            "SELECT reason, count FROM rejections WHERE host = ?1 ORDER BY count DESC",
+        )?;
+        let rejections = stmt
+            .query_map((hostname,), |row| {
+                Ok(Rejection { reason: row.get(0)?, count: row.get(1)? })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(HostRejections { hostname: hostname.to_owned(), rejections })
+    }
+
+    fn is_banned(&self, hostname: &str) -> Result<bool> {
+        Ok(self.host_status(hostname)? == Some(HostStatus::Banned))
+    }
+
+    #[cfg(not(feature = "labeler"))]
+    fn list_hosts(&self, cursor: &str, limit: u16) -> Result<ListHosts> {
+        let mut stmt = self.relay_conn.prepare_cached(
+            "SELECT host, cursor, accounts, status FROM hosts
+             WHERE host > ?1 ORDER BY host LIMIT ?2",
+        )?;
+        let hosts =
+            stmt.query_map((cursor, limit), host_from_row)?.collect::<Result<Vec<_>, _>>()?;
+        let cursor = if hosts.len() == usize::from(limit) {
+            hosts.last().map(|host| host.hostname.clone())
+        } else {
+            None
+        };
+        Ok(ListHosts { cursor, hosts })
+    }
+
+    #[cfg(not(feature = "labeler"))]
+    fn get_host_status(&self, hostname: &str) -> Result<Option<Host>> {
+        let mut stmt = self
+            .relay_conn
+            .prepare_cached("SELECT host, cursor, accounts, status FROM hosts WHERE host = ?1")?;
+        Ok(stmt.query_one((hostname,), host_from_row).optional()?)
+    }
+
     #[cfg(not(feature = "labeler"))]
     fn query_hosts(&mut self) -> Result<()> {
         let client = reqwest::blocking::Client::builder()
@@ -274,13 +634,17 @@ impl Server {
             if let Some(cursor) = &cursor {
                 params.push(("cursor", cursor));
             }
-            let url =
-                Url::parse_with_params(&format!("https://{HOSTS_RELAY}{PATH_LIST_HOSTS}"), params)?;
+            let url = Url::parse_with_params(
+                &format!("https://{}{PATH_LIST_HOSTS}", self.hosts_relay),
+                params,
+            )?;
             let mut hosts: ListHosts = client.get(url).send()?.json()?;
             hosts.hosts.sort_unstable_by_key(|host| host.account_count);
             for host in hosts.hosts.into_iter().rev() {
                 if host.account_count > HOSTS_MIN_ACCOUNTS
                     && matches!(host.status, HostStatus::Active | HostStatus::Idle)
+                    && self.check_hostname(&host.hostname).is_ok()
+                    && !self.is_banned(&host.hostname)?
                 {
                     self.request_crawl_tx
                         .push(RequestCrawl { hostname: host.hostname, cursor: None })?;
@@ -300,6 +664,9 @@ impl Server {
             self.conn.prepare_cached("SELECT DISTINCT labeler_endpoint FROM plc_labelers")?;
         for res in stmt.query_map([], |row| row.get::<_, String>(0))? {
             if let Some(hostname) = res?.strip_prefix("https://").map(|x| x.trim_end_matches('/')) {
+                if self.check_hostname(hostname).is_err() || self.is_banned(hostname)? {
+                    continue;
+                }
                 self.request_crawl_tx
                     .push(RequestCrawl { hostname: hostname.to_owned(), cursor: None })?;
             }
@@ -308,3 +675,85 @@ impl Server {
         Ok(())
     }
 }
+
+/// Checks that `hostname` answers `describeServer` and `listRepos` like a PDS.
+///
+/// The host is resolved up front so the requests can't be steered to private addresses.
+#[cfg(not(feature = "labeler"))]
+fn verify_host(hostname: &str) -> Result<()> {
+    let addrs =
+        (hostname, 443).to_socket_addrs()?.filter(|addr| is_public(addr.ip())).collect::<Vec<_>>();
+    if addrs.is_empty() {
+        return Err(eyre!("no public address"));
+    }
+    let client = reqwest::blocking::Client::builder()
+        .user_agent("rsky-relay")
+        .https_only(true)
+        .timeout(HOSTS_VERIFY_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve_to_addrs(hostname, &addrs)
+        .build()?;
+    let describe: DescribeServer = client
+        .get(format!("https://{hostname}/xrpc/com.atproto.server.describeServer"))
+        .send()?
+        .error_for_status()?
+        .json()?;
+    if !describe.did.starts_with("did:") {
+        return Err(eyre!("invalid did: {}", describe.did));
+    }
+    let repos: serde_json::Value = client
+        .get(format!("https://{hostname}/xrpc/com.atproto.sync.listRepos?limit=1"))
+        .send()?
+        .error_for_status()?
+        .json()?;
+    if !repos.get("repos").is_some_and(serde_json::Value::is_array) {
+        return Err(eyre!("invalid listRepos response"));
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "labeler"))]
+fn host_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Host> {
+    Ok(Host {
+        account_count: row.get("accounts")?,
+        hostname: row.get("host")?,
+        seq: row.get("cursor")?,
+        status: row.get("status")?,
+    })
+}
+
+fn respond(
+    mut stream: ErrorOnDropTcpStream, status: &str, content_type: &str, body: &[u8],
+) -> Result<()> {
+    #[expect(clippy::unwrap_used)]
+    let mut stream = stream.0.take().unwrap();
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: {content_type}\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()?;
+    stream.shutdown()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "labeler"))]
+fn respond_json<T: Serialize>(stream: ErrorOnDropTcpStream, body: &T) -> Result<()> {
+    respond(stream, "200 OK", "application/json", &serde_json::to_vec(body)?)
+}
+
+fn respond_error(
+    stream: ErrorOnDropTcpStream, status: &str, error: &str, message: &str,
+) -> Result<()> {
+    let body = serde_json::to_vec(&XrpcError { error, message })?;
+    respond(stream, status, "application/json", &body)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
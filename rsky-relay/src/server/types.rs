@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 
+use crate::types::HostStatus;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ListHosts {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub cursor: Option<String>,
     pub hosts: Vec<Host>,
 }
@@ -15,12 +18,36 @@ pub struct Host {
     pub status: HostStatus,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum HostStatus {
-    Active,
-    Idle,
-    Offline,
-    Throttled,
-    Banned,
+#[derive(Debug, Serialize)]
+pub struct XrpcError<'a> {
+    pub error: &'a str,
+    pub message: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminHost {
+    pub hostname: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HostRejections {
+    pub hostname: String,
+    pub rejections: Vec<Rejection>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Rejection {
+    pub reason: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminAccount {
+    pub did: String,
+}
+
+#[cfg(not(feature = "labeler"))]
+#[derive(Debug, Deserialize)]
+pub struct DescribeServer {
+    pub did: String,
 }
@@ -1,40 +1,53 @@
 use std::fmt;
 use std::ops::{Add, Sub};
-use std::sync::LazyLock;
+use std::str::FromStr;
 
 use bytes::Bytes;
 use fjall::compaction::{Fifo, Strategy};
 use fjall::{Keyspace, PartitionCreateOptions, Slice};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
 use thingbuf::{Recycle, mpsc};
 
-use crate::config::{
-    BLOCK_SIZE, CACHE_SIZE, DISK_SIZE, FSYNC_MS, MEMTABLE_SIZE, TTL_SECONDS, WRITE_BUFFER_SIZE,
-};
+use crate::config::{Config, RELAY_DB_BUSY_TIMEOUT};
 
 pub type MessageSender = mpsc::blocking::Sender<Message, MessageRecycle>;
 pub type MessageReceiver = mpsc::blocking::Receiver<Message, MessageRecycle>;
 
-#[expect(clippy::unwrap_used)]
-pub static DB: LazyLock<Keyspace> = LazyLock::new(|| {
-    let db = fjall::Config::new("db")
-        .cache_size(CACHE_SIZE)
-        .max_write_buffer_size(WRITE_BUFFER_SIZE)
-        .fsync_ms(FSYNC_MS)
-        .open()
-        .unwrap();
-    db.open_partition("firehose", firehose_options()).unwrap();
-    db.open_partition("queue", PartitionCreateOptions::default()).unwrap();
-    #[cfg(not(feature = "labeler"))]
-    db.open_partition("repos", PartitionCreateOptions::default()).unwrap();
-    db
-});
-
-fn firehose_options() -> PartitionCreateOptions {
+/// Opens the fjall keyspace shared by the validator and the publisher workers.
+pub fn open_db(config: &Config) -> Result<Keyspace, fjall::Error> {
+    let db = fjall::Config::new(&config.db_path)
+        .cache_size(config.cache_size)
+        .max_write_buffer_size(config.write_buffer_size)
+        .fsync_ms((config.fsync_ms != 0).then_some(config.fsync_ms))
+        .open()?;
+    db.open_partition("firehose", firehose_options(config))?;
+    db.open_partition("queue", PartitionCreateOptions::default())?;
+    Ok(db)
+}
+
+/// Opens `relay.db`, written by both the validator and the crawler.
+///
+/// Writable connections switch it to WAL so the server's reads never block them, and every
+/// connection waits out the others' locks instead of failing with `SQLITE_BUSY`.
+pub fn open_relay_db(config: &Config, flags: OpenFlags) -> Result<Connection, rusqlite::Error> {
+    let conn =
+        Connection::open_with_flags(&config.relay_db, flags | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+    conn.busy_timeout(RELAY_DB_BUSY_TIMEOUT)?;
+    if flags.contains(OpenFlags::SQLITE_OPEN_READ_WRITE) {
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+    }
+    Ok(conn)
+}
+
+fn firehose_options(config: &Config) -> PartitionCreateOptions {
+    let ttl_seconds = (config.ttl_seconds != 0).then_some(config.ttl_seconds);
     PartitionCreateOptions::default()
         .manual_journal_persist(true)
-        .compaction_strategy(Strategy::Fifo(Fifo::new(DISK_SIZE, TTL_SECONDS)))
-        .max_memtable_size(MEMTABLE_SIZE)
-        .block_size(BLOCK_SIZE)
+        .compaction_strategy(Strategy::Fifo(Fifo::new(config.disk_size, ttl_seconds)))
+        .max_memtable_size(config.memtable_size)
+        .block_size(config.block_size)
 }
 
 #[derive(Debug)]
@@ -54,6 +67,64 @@ impl Recycle<Message> for MessageRecycle {
     fn recycle(&self, _: &mut Message) {}
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostStatus {
+    #[default]
+    Active,
+    Idle,
+    Offline,
+    Throttled,
+    Banned,
+}
+
+impl HostStatus {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Idle => "idle",
+            Self::Offline => "offline",
+            Self::Throttled => "throttled",
+            Self::Banned => "banned",
+        }
+    }
+}
+
+impl fmt::Display for HostStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HostStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(Self::Active),
+            "idle" => Ok(Self::Idle),
+            "offline" => Ok(Self::Offline),
+            "throttled" => Ok(Self::Throttled),
+            "banned" => Ok(Self::Banned),
+            _ => Err(()),
+        }
+    }
+}
+
+impl ToSql for HostStatus {
+    #[inline]
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for HostStatus {
+    #[inline]
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value.as_str()?.parse().map_err(|()| FromSqlError::InvalidType)
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Default)]
 pub struct Cursor([u8; 8]);
 
@@ -6,6 +6,7 @@ use std::{fmt, io};
 use chrono::{DateTime, Utc};
 use cid::Cid;
 use rs_car_sync::CarDecodeError;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use vec1::Vec1;
@@ -33,6 +34,8 @@ pub enum ParseError {
     Commit(#[from] serde_ipld_dagcbor::DecodeError<Infallible>),
     #[error("unknown type: {0}")]
     UnknownType(String),
+    #[error("tree too deep")]
+    TooDeep,
 }
 
 #[derive(Debug, Error)]
@@ -57,12 +60,47 @@ pub enum AccountStatus {
     Throttled,
 }
 
+impl AccountStatus {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Takendown => "takendown",
+            Self::Suspended => "suspended",
+            Self::Deleted => "deleted",
+            Self::Deactivated => "deactivated",
+            Self::Desynchronized => "desynchronized",
+            Self::Throttled => "throttled",
+        }
+    }
+}
+
 impl fmt::Display for AccountStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{self:?}")
     }
 }
 
+impl ToSql for AccountStatus {
+    #[inline]
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for AccountStatus {
+    #[inline]
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "takendown" => Ok(Self::Takendown),
+            "suspended" => Ok(Self::Suspended),
+            "deleted" => Ok(Self::Deleted),
+            "deactivated" => Ok(Self::Deactivated),
+            "desynchronized" => Ok(Self::Desynchronized),
+            "throttled" => Ok(Self::Throttled),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Commit {
     pub did: String,
@@ -184,7 +222,33 @@ pub struct SubscribeReposAccount {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SubscribeReposInfo {
     pub name: String,
-    pub message: String,
+    pub message: Option<String>,
+}
+
+/// Body of an error frame (`op: -1`), sent right before the upstream closes the stream.
+#[derive(Debug, Deserialize)]
+pub struct SubscribeReposError {
+    pub error: String,
+    pub message: Option<String>,
+}
+
+/// Frames that aren't relayed, but change how an upstream is followed.
+#[derive(Debug)]
+pub enum Control {
+    Info(SubscribeReposInfo),
+    Error(SubscribeReposError),
+}
+
+impl Control {
+    pub fn parse(data: &[u8]) -> Result<Option<Self>, ParseError> {
+        let mut reader = io::Cursor::new(data);
+        let header = ciborium::de::from_reader::<Header<'static>, _>(&mut reader)?;
+        Ok(match (header.operation_, header.type_.as_ref()) {
+            (-1, _) => Some(Self::Error(serde_ipld_dagcbor::from_reader(&mut reader)?)),
+            (_, "#info") => Some(Self::Info(serde_ipld_dagcbor::from_reader(&mut reader)?)),
+            _ => None,
+        })
+    }
 }
 
 /// Subscribe to stream of labels (and negations). Public endpoint implemented by mod services.
@@ -243,6 +307,14 @@ pub struct Header<'a> {
     pub operation_: i8,
 }
 
+impl Header<'_> {
+    /// Whether the frame is an error frame, the last one before the upstream closes the stream.
+    pub fn is_error(data: &[u8]) -> bool {
+        ciborium::de::from_reader::<Header<'static>, _>(data)
+            .is_ok_and(|header| header.operation_ == -1)
+    }
+}
+
 impl SubscribeReposEvent {
     pub fn parse(data: &[u8]) -> Result<Option<Self>, ParseError> {
         let mut reader = io::Cursor::new(data);
@@ -268,9 +340,8 @@ impl SubscribeReposEvent {
                 }
                 Self::Labels(labels)
             }
+            // see `Control`
             "#info" => {
-                let info = serde_ipld_dagcbor::from_reader::<SubscribeReposInfo, _>(&mut reader)?;
-                tracing::debug!(name = %info.name, message = %info.message, "received #info");
                 return Ok(None);
             }
             _ => {
@@ -362,3 +433,35 @@ impl SubscribeReposEvent {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::validator::event::{Control, Header, SubscribeReposEvent};
+
+    #[test]
+    fn future_cursor() {
+        // `{"op": -1}`, `{"error": "FutureCursor", "message": "Cursor in the future."}`
+        let frame = b"\xa1bop \xa2eerrorlFutureCursorgmessageuCursor in the future.";
+        assert!(Header::is_error(frame));
+        assert!(matches!(SubscribeReposEvent::parse(frame), Ok(None)));
+        let Ok(Some(Control::Error(err))) = Control::parse(frame) else {
+            panic!("expected an error frame");
+        };
+        assert_eq!(err.error, "FutureCursor");
+        assert_eq!(err.message.as_deref(), Some("Cursor in the future."));
+    }
+
+    #[test]
+    fn info() {
+        // `{"t": "#info", "op": 1}`, `{"name": "OutdatedCursor"}`
+        let frame = b"\xa2at\x65#infobop\x01\xa1dnamenOutdatedCursor";
+        assert!(!Header::is_error(frame));
+        assert!(matches!(SubscribeReposEvent::parse(frame), Ok(None)));
+        let Ok(Some(Control::Info(info))) = Control::parse(frame) else {
+            panic!("expected an info frame");
+        };
+        assert_eq!(info.name, "OutdatedCursor");
+        assert!(info.message.is_none());
+        assert!(!Header::is_error(b""));
+    }
+}
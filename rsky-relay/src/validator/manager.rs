@@ -4,23 +4,47 @@ use std::thread;
 use std::time::{Duration, Instant, SystemTimeError};
 
 use chrono::{DateTime, Utc};
-use fjall::{Batch, PartitionCreateOptions, PartitionHandle, PersistMode};
+use fjall::{Batch, Keyspace, PartitionCreateOptions, PartitionHandle, PersistMode};
 use hashbrown::HashMap;
 #[cfg(not(feature = "labeler"))]
 use hashbrown::hash_map::Entry;
-use rusqlite::Connection;
+use rtrb::{Consumer, Producer};
+use rusqlite::{Connection, OpenFlags};
 use thiserror::Error;
 
 use crate::SHUTDOWN;
-use crate::config::HOSTS_WRITE_INTERVAL;
-use crate::types::{Cursor, DB, MessageReceiver};
-use crate::validator::event::{ParseError, SerializeError, SubscribeReposEvent};
+#[cfg(not(feature = "labeler"))]
+use crate::config::REPOS_RESYNC_RETRY;
+use crate::config::{Config, HOSTS_WRITE_INTERVAL};
+use crate::crawler::CursorSender;
+use crate::metrics::METRICS;
+use crate::types::{Cursor, MessageReceiver, open_relay_db};
+#[cfg(not(feature = "labeler"))]
+use crate::validator::event::SubscribeReposAccount;
+use crate::validator::event::{
+    AccountStatus, Control, ParseError, SerializeError, SubscribeReposEvent,
+};
+#[cfg(not(feature = "labeler"))]
+use crate::validator::repos::RepoStore;
 use crate::validator::resolver::{Resolver, ResolverError};
 #[cfg(not(feature = "labeler"))]
+use crate::validator::resync::{ResyncError, Resynced, Resyncer};
+#[cfg(not(feature = "labeler"))]
 use crate::validator::types::RepoState;
-use crate::validator::utils;
+use crate::validator::utils::{self, ValidationError};
 
 const SLEEP: Duration = Duration::from_micros(100);
+#[cfg(not(feature = "labeler"))]
+const REPOS_MIGRATE_BATCH: u64 = 100_000;
+
+pub type AccountSender = Producer<AccountCommand>;
+pub type AccountReceiver = Consumer<AccountCommand>;
+
+#[derive(Debug)]
+pub enum AccountCommand {
+    Takedown { did: String, status: AccountStatus },
+    Restore { did: String },
+}
 
 #[derive(Debug, Error)]
 pub enum ManagerError {
@@ -30,6 +54,9 @@ pub enum ManagerError {
     Serialize(#[from] SerializeError),
     #[error("resolver error: {0}")]
     Resolver(#[from] ResolverError),
+    #[cfg(not(feature = "labeler"))]
+    #[error("resync error: {0}")]
+    Resync(#[from] ResyncError),
     #[error("time error: {0}")]
     Time(#[from] SystemTimeError),
     #[error("sqlite error: {0}")]
@@ -40,78 +67,215 @@ pub enum ManagerError {
     DecodeError(#[from] serde_ipld_dagcbor::DecodeError<Infallible>),
 }
 
+#[derive(Debug, Default)]
+struct HostState {
+    cursor: Cursor,
+    latest: DateTime<Utc>,
+    accounts: u64,
+    /// rejections since the last persist, keyed by reason
+    rejections: HashMap<ValidationError, u64>,
+}
+
+impl HostState {
+    #[inline]
+    const fn update(&mut self, cursor: Cursor, latest: DateTime<Utc>) {
+        self.cursor = cursor;
+        self.latest = latest;
+    }
+
+    #[inline]
+    fn reject(&mut self, type_: &str, err: ValidationError) {
+        tracing::debug!(%err, "rejected event");
+        METRICS.rejected(type_);
+        *self.rejections.entry(err).or_default() += 1;
+    }
+}
+
 pub struct Manager {
     message_rx: MessageReceiver,
-    hosts: HashMap<String, (Cursor, DateTime<Utc>)>,
+    hosts: HashMap<String, HostState>,
+    #[cfg(not(feature = "labeler"))]
+    repos: RepoStore,
+    takedowns: HashMap<String, AccountStatus>,
+    /// repos whose commits stopped chaining, with the time of the last resync request
+    #[cfg(not(feature = "labeler"))]
+    desynced: HashMap<String, Instant>,
+    /// desynchronized repos not yet written, with their host, or `None` once resynchronized
     #[cfg(not(feature = "labeler"))]
-    repos: HashMap<String, RepoState>,
+    desynced_dirty: HashMap<String, Option<String>>,
+    #[cfg(not(feature = "labeler"))]
+    host_max_accounts: u64,
+    /// per-host overrides of `host_max_accounts`
+    #[cfg(not(feature = "labeler"))]
+    account_limits: HashMap<String, u64>,
     resolver: Resolver,
+    #[cfg(not(feature = "labeler"))]
+    resyncer: Resyncer,
+    #[cfg(not(feature = "labeler"))]
+    strict_mst: bool,
+    #[cfg(not(feature = "labeler"))]
+    resync_new_repos: bool,
+    max_clock_skew: Duration,
     last: Instant,
     conn: Connection,
+    cursor_tx: CursorSender,
+    db: Keyspace,
     queue: PartitionHandle,
     firehose: PartitionHandle,
+    account_rx: AccountReceiver,
 }
 
 impl Manager {
-    pub fn new(message_rx: MessageReceiver) -> Result<Self, ManagerError> {
+    pub fn new(
+        config: &Config, db: Keyspace, message_rx: MessageReceiver, account_rx: AccountReceiver,
+        cursor_tx: CursorSender,
+    ) -> Result<Self, ManagerError> {
         let hosts = HashMap::new();
+        let resolver = Resolver::new(config)?;
         #[cfg(not(feature = "labeler"))]
-        let repos = HashMap::new();
-        let resolver = Resolver::new()?;
+        let resyncer = Resyncer::new(config)?;
         let now = Instant::now();
         let last = now.checked_sub(HOSTS_WRITE_INTERVAL).unwrap_or(now);
-        let conn = Connection::open("relay.db")?;
+        let conn = open_relay_db(
+            config,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        )?;
         conn.execute(
             "CREATE TABLE IF NOT EXISTS hosts (
                 host TEXT PRIMARY KEY,
                 cursor INTEGER NOT NULL,
-                latest TEXT NOT NULL
+                latest TEXT NOT NULL,
+                accounts INTEGER NOT NULL DEFAULT 0,
+                status TEXT NOT NULL DEFAULT 'active',
+                failures INTEGER NOT NULL DEFAULT 0,
+                retry_at TEXT
+            )",
+            (),
+        )?;
+        add_column(&conn, "hosts", "accounts INTEGER NOT NULL DEFAULT 0")?;
+        add_column(&conn, "hosts", "status TEXT NOT NULL DEFAULT 'active'")?;
+        // crawler backoff, kept across restarts
+        add_column(&conn, "hosts", "failures INTEGER NOT NULL DEFAULT 0")?;
+        add_column(&conn, "hosts", "retry_at TEXT")?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS takedowns (
+                did TEXT PRIMARY KEY,
+                status TEXT NOT NULL,
+                created TEXT NOT NULL
+            )",
+            (),
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS rejections (
+                host TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                count INTEGER NOT NULL,
+                PRIMARY KEY (host, reason)
+            )",
+            (),
+        )?;
+        // upstream events lost for good, eg after the host no longer had our cursor
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS gaps (
+                host TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                cursor INTEGER NOT NULL,
+                time TEXT NOT NULL
+            )",
+            (),
+        )?;
+        // NULL columns fall back to the configured defaults
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS host_limits (
+                host TEXT PRIMARY KEY,
+                per_second INTEGER,
+                per_hour INTEGER,
+                max_accounts INTEGER
+            )",
+            (),
+        )?;
+        // repos waiting on a resync, their commits are dropped until then
+        #[cfg(not(feature = "labeler"))]
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS desynced (
+                did TEXT PRIMARY KEY,
+                host TEXT NOT NULL
             )",
             (),
         )?;
-        let queue = DB.open_partition("queue", PartitionCreateOptions::default())?;
-        let firehose = DB.open_partition("firehose", PartitionCreateOptions::default())?;
+        #[cfg(not(feature = "labeler"))]
+        let repos = RepoStore::new(config, &conn)?;
+        let queue = db.open_partition("queue", PartitionCreateOptions::default())?;
+        let firehose = db.open_partition("firehose", PartitionCreateOptions::default())?;
         Ok(Self {
             message_rx,
             hosts,
             #[cfg(not(feature = "labeler"))]
             repos,
+            takedowns: HashMap::new(),
+            #[cfg(not(feature = "labeler"))]
+            desynced: HashMap::new(),
+            #[cfg(not(feature = "labeler"))]
+            desynced_dirty: HashMap::new(),
+            #[cfg(not(feature = "labeler"))]
+            host_max_accounts: config.host_max_accounts,
+            #[cfg(not(feature = "labeler"))]
+            account_limits: HashMap::new(),
             resolver,
+            #[cfg(not(feature = "labeler"))]
+            resyncer,
+            #[cfg(not(feature = "labeler"))]
+            strict_mst: config.strict_mst,
+            #[cfg(not(feature = "labeler"))]
+            resync_new_repos: config.resync_new_repos,
+            max_clock_skew: config.max_clock_skew(),
             last,
             conn,
+            cursor_tx,
+            db,
             queue,
             firehose,
+            account_rx,
         })
     }
 
     pub async fn run(mut self) -> Result<(), ManagerError> {
         let mut hosts = 0;
         {
-            let mut stmt = self.conn.prepare_cached("SELECT host, cursor FROM hosts")?;
+            let mut stmt = self.conn.prepare_cached("SELECT host, cursor, accounts FROM hosts")?;
             let mut rows = stmt.query(())?;
             while let Some(row) = rows.next()? {
                 let host = row.get_unwrap("host");
                 let cursor: u64 = row.get_unwrap("cursor");
-                self.hosts.insert(host, (cursor.into(), DateTime::UNIX_EPOCH));
+                let accounts = row.get_unwrap("accounts");
+                self.hosts.insert(
+                    host,
+                    HostState {
+                        cursor: cursor.into(),
+                        latest: DateTime::UNIX_EPOCH,
+                        accounts,
+                        ..HostState::default()
+                    },
+                );
                 hosts += 1;
             }
         }
-        #[allow(unused_mut)]
-        let mut repos = 0;
-        #[cfg(not(feature = "labeler"))]
         {
-            // TODO: move this to sqlite
-            let handle = DB.open_partition("repos", PartitionCreateOptions::default())?;
-            self.repos.reserve(handle.approximate_len());
-            for res in handle.iter() {
-                let (did, state) = res?;
-                #[expect(clippy::unwrap_used)]
-                let did = String::from_utf8(did.to_vec()).unwrap();
-                let state = serde_ipld_dagcbor::from_slice(&state)?;
-                self.repos.insert(did, state);
-                repos += 1;
+            let mut stmt = self.conn.prepare_cached("SELECT did, status FROM takedowns")?;
+            let mut rows = stmt.query(())?;
+            while let Some(row) = rows.next()? {
+                self.takedowns.insert(row.get_unwrap("did"), row.get_unwrap("status"));
             }
         }
+        let takedowns = self.takedowns.len();
+        #[cfg(not(feature = "labeler"))]
+        self.load_limits()?;
+        #[cfg(not(feature = "labeler"))]
+        {
+            self.migrate_repos()?;
+            self.migrate_inactive()?;
+            self.load_desynced()?;
+        }
 
         let mut cursor = self.firehose.last_key_value()?.map(|(k, _)| k.into()).unwrap_or_default();
         let mut queue_drained = 0;
@@ -130,32 +294,188 @@ impl Manager {
             }
         }
 
-        tracing::info!(%hosts, %repos, %queue_drained, %queue_pending, %cursor, "loaded state");
+        tracing::info!(%hosts, %takedowns, %queue_drained, %queue_pending, %cursor, "loaded state");
         while self.update(&mut cursor).await? {}
         tracing::info!("shutting down validator");
         SHUTDOWN.store(true, Ordering::Relaxed);
         Ok(())
     }
 
+    // desynchronized repos are requested again, and retried on their next dropped commit if their
+    // did is still resolving
+    #[cfg(not(feature = "labeler"))]
+    fn load_desynced(&mut self) -> Result<(), ManagerError> {
+        let mut desynced = Vec::new();
+        {
+            let mut stmt = self.conn.prepare_cached("SELECT did FROM desynced")?;
+            let mut rows = stmt.query(())?;
+            while let Some(row) = rows.next()? {
+                desynced.push(row.get_unwrap::<_, String>("did"));
+            }
+        }
+        let now = Instant::now();
+        let retry = now.checked_sub(REPOS_RESYNC_RETRY).unwrap_or(now);
+        for did in desynced {
+            self.request_resync(&did, true)?;
+            self.desynced.insert(did, retry);
+        }
+        if !self.desynced.is_empty() {
+            tracing::info!(repos = %self.desynced.len(), "resyncing desynchronized repos");
+        }
+        Ok(())
+    }
+
+    // repo states used to be kept in fjall and loaded in full at startup
+    #[cfg(not(feature = "labeler"))]
+    fn migrate_repos(&mut self) -> Result<(), ManagerError> {
+        if !self.db.partition_exists("repos") {
+            return Ok(());
+        }
+        let handle = self.db.open_partition("repos", PartitionCreateOptions::default())?;
+        let mut repos = 0;
+        for res in handle.iter() {
+            let (did, state) = res?;
+            #[expect(clippy::unwrap_used)]
+            let did = String::from_utf8(did.to_vec()).unwrap();
+            self.repos.insert(&self.conn, did, serde_ipld_dagcbor::from_slice(&state)?)?;
+            repos += 1;
+            if repos % REPOS_MIGRATE_BATCH == 0 {
+                self.repos.flush(&mut self.conn)?;
+            }
+        }
+        self.repos.flush(&mut self.conn)?;
+        self.db.delete_partition(handle)?;
+        tracing::info!(%repos, "migrated repo states to sqlite");
+        Ok(())
+    }
+
+    // account statuses used to be kept in fjall and loaded in full at startup
+    #[cfg(not(feature = "labeler"))]
+    fn migrate_inactive(&mut self) -> Result<(), ManagerError> {
+        if !self.db.partition_exists("inactive") {
+            return Ok(());
+        }
+        let handle = self.db.open_partition("inactive", PartitionCreateOptions::default())?;
+        let mut accounts = 0;
+        for res in handle.iter() {
+            let (did, status) = res?;
+            #[expect(clippy::unwrap_used)]
+            let did = String::from_utf8(did.to_vec()).unwrap();
+            let status = serde_ipld_dagcbor::from_slice(&status)?;
+            self.repos.set_active(&self.conn, did, false, status)?;
+            accounts += 1;
+            if accounts % REPOS_MIGRATE_BATCH == 0 {
+                self.repos.flush(&mut self.conn)?;
+            }
+        }
+        self.repos.flush(&mut self.conn)?;
+        self.db.delete_partition(handle)?;
+        tracing::info!(%accounts, "migrated account statuses to sqlite");
+        Ok(())
+    }
+
     fn persist(&mut self) -> Result<(), ManagerError> {
         // persist hosts data
         let tx = self.conn.transaction()?;
         let mut stmt = tx.prepare_cached(
             "
-                INSERT INTO hosts (host, cursor, latest)
-                VALUES (?1, ?2, ?3)
+                INSERT INTO hosts (host, cursor, latest, accounts)
+                VALUES (?1, ?2, ?3, ?4)
                 ON CONFLICT(host)
-                DO UPDATE SET cursor = excluded.cursor, latest = excluded.latest
+                DO UPDATE SET
+                    cursor = excluded.cursor,
+                    latest = excluded.latest,
+                    accounts = excluded.accounts
             ",
         )?;
-        for (host, (cursor, time)) in &self.hosts {
-            if *time != DateTime::UNIX_EPOCH {
-                stmt.execute((host, cursor.get(), time))?;
+        for (host, state) in &self.hosts {
+            if state.latest != DateTime::UNIX_EPOCH {
+                stmt.execute((host, state.cursor.get(), state.latest, state.accounts))?;
             }
         }
         drop(stmt);
+        // persist rejection counters
+        let mut stmt = tx.prepare_cached(
+            "
+                INSERT INTO rejections (host, reason, count)
+                VALUES (?1, ?2, ?3)
+                ON CONFLICT(host, reason)
+                DO UPDATE SET count = count + excluded.count
+            ",
+        )?;
+        for (host, state) in &self.hosts {
+            for (reason, count) in &state.rejections {
+                stmt.execute((host, reason.as_str(), count))?;
+            }
+        }
+        drop(stmt);
+        // persist desynchronized repos
+        #[cfg(not(feature = "labeler"))]
+        {
+            let mut insert =
+                tx.prepare_cached("INSERT OR REPLACE INTO desynced (did, host) VALUES (?1, ?2)")?;
+            let mut delete = tx.prepare_cached("DELETE FROM desynced WHERE did = ?1")?;
+            for (did, host) in &self.desynced_dirty {
+                if let Some(host) = host {
+                    insert.execute((did, host))?;
+                } else {
+                    delete.execute((did,))?;
+                }
+            }
+        }
         tx.commit()?;
+        // only cleared once written, a failed persist keeps counting towards the next one
+        for state in self.hosts.values_mut() {
+            state.rejections.clear();
+        }
+        #[cfg(not(feature = "labeler"))]
+        self.desynced_dirty.clear();
+        // persist repo states
+        #[cfg(not(feature = "labeler"))]
+        self.repos.flush(&mut self.conn)?;
+
+        Ok(())
+    }
+
+    fn handle_control(&mut self, host: &str, control: Control) -> Result<(), ManagerError> {
+        match control {
+            Control::Info(info) if info.name == "OutdatedCursor" => {
+                // the host skipped ahead to the oldest event it still has
+                tracing::warn!(message = ?info.message, "outdated cursor");
+                self.record_gap(host, "outdated_cursor")?;
+            }
+            Control::Info(info) => {
+                tracing::debug!(name = %info.name, message = ?info.message, "received #info");
+            }
+            Control::Error(err) if err.error == "FutureCursor" => {
+                // the host's seq was reset, the crawler reconnects from its oldest event
+                tracing::warn!(message = ?err.message, "future cursor, resetting");
+                self.record_gap(host, "future_cursor")?;
+                self.hosts.entry_ref(host).or_default().cursor = 0.into();
+            }
+            Control::Error(err) => {
+                tracing::info!(error = %err.error, message = ?err.message, "upstream error");
+            }
+        }
+        Ok(())
+    }
 
+    fn record_gap(&self, host: &str, reason: &str) -> Result<(), ManagerError> {
+        let cursor = self.hosts.get(host).map_or(0, |state| state.cursor.get());
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT INTO gaps (host, reason, cursor, time) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        stmt.execute((host, reason, cursor, Utc::now()))?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "labeler"))]
+    fn load_limits(&mut self) -> Result<(), ManagerError> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT host, max_accounts FROM host_limits WHERE max_accounts IS NOT NULL",
+        )?;
+        self.account_limits =
+            stmt.query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<Result<_, _>>()?;
         Ok(())
     }
 
@@ -167,10 +487,28 @@ impl Manager {
 
         let now = Instant::now();
         if self.last + HOSTS_WRITE_INTERVAL < now {
-            self.persist()?;
+            // retried at the next interval, the state is still in memory
+            if let Err(err) = self.persist() {
+                tracing::warn!(%err, "unable to persist state");
+            }
+            // picks up overrides edited while running
+            #[cfg(not(feature = "labeler"))]
+            self.load_limits()?;
             self.last = now;
+            METRICS.queue_len.store(self.queue.approximate_len() as u64, Ordering::Relaxed);
         }
 
+        while let Ok(command) = self.account_rx.pop() {
+            self.handle_account(cursor, command)?;
+        }
+
+        // applied after the loop, as `msg` borrows `message_rx`
+        #[cfg(not(feature = "labeler"))]
+        let mut desyncs = Vec::new();
+        #[cfg(not(feature = "labeler"))]
+        let mut resyncs = Vec::new();
+        #[cfg(not(feature = "labeler"))]
+        let mut unverified = Vec::new();
         for _ in 0..1024 {
             let msg = match self.message_rx.try_recv_ref() {
                 Ok(msg) => msg,
@@ -185,11 +523,34 @@ impl Manager {
             let host = &msg.hostname;
             let span = tracing::info_span!("msg_recv", %host, len = %msg.data.len());
             let _enter = span.enter();
+            if msg.data.is_empty() {
+                // the crawler is moving the host to another worker, and waits for our cursor
+                let cursor = self.hosts.get(host).map(|state| state.cursor);
+                if self.cursor_tx.push((host.clone(), cursor)).is_err() {
+                    tracing::warn!("cursor channel full");
+                }
+                continue;
+            }
             let event = match SubscribeReposEvent::parse(&msg.data) {
                 Ok(Some(event)) => event,
-                Ok(None) => continue,
+                Ok(None) => {
+                    let control = Control::parse(&msg.data);
+                    // frees `message_rx` for `handle_control`, control frames are rare
+                    let host = msg.hostname.clone();
+                    drop(msg);
+                    match control {
+                        Ok(Some(control)) => self.handle_control(&host, control)?,
+                        Ok(None) => {}
+                        Err(err) => tracing::debug!(%err, "invalid control frame"),
+                    }
+                    continue;
+                }
                 Err(err) => {
-                    tracing::debug!(%err, "parse error");
+                    tracing::trace!(%err, "parse error");
+                    self.hosts
+                        .entry_ref(host.as_str())
+                        .or_default()
+                        .reject("unknown", ValidationError::Parse);
                     continue;
                 }
             };
@@ -201,9 +562,10 @@ impl Manager {
             let did = event.did();
             let span = tracing::debug_span!("msg_data", type = %type_, %seq, %time, %did);
             let _enter = span.enter();
-            if let Some((prev, old)) = self.hosts.get(host) {
-                time = time.max(*old);
-                let prev: u64 = (*prev).into();
+            time = utils::clamp_time(time, self.max_clock_skew);
+            if let Some(state) = self.hosts.get(host) {
+                time = time.max(state.latest);
+                let prev: u64 = state.cursor.into();
                 let curr: u64 = seq.into();
                 if prev >= curr {
                     if prev > curr {
@@ -215,6 +577,15 @@ impl Manager {
                 }
             }
 
+            // identity changes are still relayed so consumers can keep their caches fresh
+            if self.takedowns.contains_key(did)
+                && !matches!(event, SubscribeReposEvent::Identity(_))
+            {
+                tracing::debug!("dropping event for taken down account");
+                self.hosts.entry_ref(host.as_str()).or_default().update(seq, time);
+                continue;
+            }
+
             // get commit object for #commit/#sync or add to the firehose
             let span;
             let _enter;
@@ -232,7 +603,8 @@ impl Manager {
                     _enter = span.enter();
 
                     #[cfg(not(feature = "labeler"))]
-                    if !event.validate(&commit, &head) {
+                    if let Err(err) = event.validate(&commit, &head, self.max_clock_skew) {
+                        self.hosts.entry_ref(host.as_str()).or_default().reject(type_, err);
                         continue;
                     }
                     (commit, head)
@@ -241,21 +613,71 @@ impl Manager {
                     if let SubscribeReposEvent::Identity(_) = &event {
                         self.resolver.expire(did, event.time());
                     }
+                    // only the account's current pds is authoritative for its status, so it waits
+                    // for the identity like commits do
+                    #[cfg(not(feature = "labeler"))]
+                    if let SubscribeReposEvent::Account(account) = &event {
+                        match self.resolver.resolve(did)? {
+                            Some((Some(pds), _)) if pds == host => {
+                                self.repos.set_active(
+                                    &self.conn,
+                                    did.to_owned(),
+                                    account.active,
+                                    account.status.clone(),
+                                )?;
+                            }
+                            Some((Some(_), _)) => {
+                                // expire the identity & queue message in case the user has migrated
+                                self.resolver.expire(did, time);
+                                self.queue
+                                    .insert(format!("{did}>{host}>{seq}"), msg.data.to_vec())?;
+                                self.hosts.entry_ref(host.as_str()).or_default().update(seq, time);
+                                continue;
+                            }
+                            Some((None, _)) => {
+                                tracing::debug!("ignoring account status without a pds");
+                                self.hosts
+                                    .entry_ref(host.as_str())
+                                    .or_default()
+                                    .reject(type_, ValidationError::HostMismatch);
+                                continue;
+                            }
+                            None => {
+                                self.queue
+                                    .insert(format!("{did}>{host}>{seq}"), msg.data.to_vec())?;
+                                self.hosts.entry_ref(host.as_str()).or_default().update(seq, time);
+                                continue;
+                            }
+                        }
+                    }
                     let data = event.serialize(msg.data.len(), cursor.next())?;
                     self.firehose.insert(*cursor, data)?;
-                    self.hosts.insert(host.clone(), (seq, time));
+                    METRICS.accepted(type_);
+                    self.hosts.entry_ref(host.as_str()).or_default().update(seq, time);
                     continue;
                 }
                 Err(err) => {
-                    tracing::debug!(%err, "commit decode error");
+                    tracing::trace!(%err, "commit decode error");
+                    self.hosts
+                        .entry_ref(host.as_str())
+                        .or_default()
+                        .reject(type_, ValidationError::CommitDecode);
                     continue;
                 }
             };
 
+            // commits & syncs are rejected until the account is reactivated
+            #[cfg(not(feature = "labeler"))]
+            if let Some(status) = self.repos.inactive(&self.conn, did)? {
+                tracing::debug!(?status, "dropping event for inactive account");
+                self.hosts.entry_ref(host.as_str()).or_default().update(seq, time);
+                continue;
+            }
+
             // resolve identity & check pds
             let Some((pds, key)) = self.resolver.resolve(did)? else {
                 self.queue.insert(format!("{did}>{host}>{seq}"), msg.data.to_vec())?;
-                self.hosts.insert(host.clone(), (seq, time));
+                self.hosts.entry_ref(host.as_str()).or_default().update(seq, time);
                 continue;
             };
 
@@ -264,63 +686,282 @@ impl Manager {
                     // expire the identity & queue message in case the user has migrated
                     self.resolver.expire(did, time);
                     self.queue.insert(format!("{did}>{host}>{seq}"), msg.data.to_vec())?;
-                    self.hosts.insert(host.clone(), (seq, time));
+                    self.hosts.entry_ref(host.as_str()).or_default().update(seq, time);
                     continue;
                 }
             }
 
             // verify signature
             #[allow(clippy::needless_borrow)]
-            match utils::verify_commit_sig(&commit, key) {
-                Ok(valid) => {
-                    if !valid {
-                        tracing::debug!(?key, "signature mismatch");
-                        continue;
-                    }
-                }
-                Err(err) => {
-                    tracing::debug!(%err, ?key, "signature check error");
-                    continue;
-                }
+            if let Err(err) = utils::verify_commit_sig(&commit, key) {
+                tracing::trace!(?key, "signature check failed");
+                self.hosts.entry_ref(host.as_str()).or_default().reject(type_, err);
+                continue;
+            }
+
+            // commits are dropped until the repo is resynced, which is retried if it's due
+            #[cfg(not(feature = "labeler"))]
+            if self.desynced.contains_key(did) && matches!(event, SubscribeReposEvent::Commit(_)) {
+                tracing::debug!("dropping commit for desynchronized repo");
+                desyncs.push((did.to_owned(), host.clone()));
+                self.hosts.entry_ref(host.as_str()).or_default().update(seq, time);
+                continue;
             }
 
             // verify commit message
             #[cfg(not(feature = "labeler"))]
-            let (rev, data, entry) = { (commit.rev, commit.data, self.repos.entry(commit.did)) };
+            let (rev, data, repo) = (commit.rev, commit.data, commit.did);
             #[cfg(not(feature = "labeler"))]
-            if let SubscribeReposEvent::Commit(commit) = &event {
-                // TODO: should still validate records existing in blocks, etc
-                if let Entry::Occupied(prev) = &entry {
-                    let prev = prev.get();
-                    let span = tracing::debug_span!("previous", rev = %prev.rev, data = %prev.data, head = %prev.head);
-                    let _enter = span.enter();
-                    if !utils::verify_commit_event(commit, data, prev) {
-                        continue;
+            let prev = self.repos.get(&self.conn, &repo)?;
+            #[cfg(not(feature = "labeler"))]
+            let known = prev.is_some();
+            #[cfg(not(feature = "labeler"))]
+            if !known {
+                let limit = self
+                    .account_limits
+                    .get(host.as_str())
+                    .copied()
+                    .unwrap_or(self.host_max_accounts);
+                let state = self.hosts.entry_ref(host.as_str()).or_default();
+                if state.accounts >= limit {
+                    state.reject(type_, ValidationError::AccountLimit);
+                    continue;
+                }
+            }
+            // a #sync replaces the repo state, so the next #commit chains from it
+            #[cfg(not(feature = "labeler"))]
+            if let Some(prev) = prev {
+                let span = tracing::debug_span!("previous", rev = %prev.rev, data = %prev.data, head = %prev.head);
+                let _enter = span.enter();
+                let res = match &event {
+                    // TODO: should still validate records existing in blocks, etc
+                    SubscribeReposEvent::Commit(commit) => {
+                        // dropped even in lenient mode, the repo is resynced instead
+                        if utils::breaks_chain(commit, prev) {
+                            desyncs.push((did.to_owned(), host.clone()));
+                            Err(ValidationError::ChainBreak)
+                        } else {
+                            utils::verify_commit_event(commit, data, prev, self.strict_mst)
+                        }
                     }
+                    SubscribeReposEvent::Sync(_) => utils::verify_sync_event(&rev, prev),
+                    _ => Ok(()),
+                };
+                if let Err(err) = res {
+                    self.hosts.entry_ref(host.as_str()).or_default().reject(type_, err);
+                    continue;
                 }
             }
+            #[cfg(not(feature = "labeler"))]
+            if matches!(event, SubscribeReposEvent::Sync(_)) && self.desynced.contains_key(did) {
+                resyncs.push(did.to_owned());
+            }
 
             let msg = event.serialize(msg.data.len(), cursor.next())?;
             self.firehose.insert(*cursor, msg)?;
+            METRICS.accepted(type_);
+            let state = self.hosts.entry_ref(host.as_str()).or_default();
+            #[cfg(not(feature = "labeler"))]
+            if !known {
+                state.accounts += 1;
+                // first seen mid-stream, so the repo is checked in full against its pds
+                if self.resync_new_repos {
+                    unverified.push(repo.clone());
+                }
+            }
             #[cfg(not(feature = "labeler"))]
-            entry.insert(RepoState { rev, data, head });
-            self.hosts.insert(host.clone(), (seq, time));
+            self.repos.insert(&self.conn, repo, RepoState { rev, data, head })?;
+            state.update(seq, time);
+        }
+
+        #[cfg(not(feature = "labeler"))]
+        {
+            for (did, host) in desyncs {
+                self.desync(cursor, did, &host)?;
+            }
+            for did in resyncs {
+                self.resynced(cursor, &did)?;
+            }
+            for did in unverified {
+                self.request_resync(&did, false)?;
+            }
+            if let Some((did, res)) = self.resyncer.poll().await {
+                self.resync(cursor, did, res)?;
+            }
         }
 
         for did in self.resolver.poll().await? {
             self.scan_did(cursor, &did)?;
         }
+        METRICS.resolver_inflight.store(self.resolver.inflight() as u64, Ordering::Relaxed);
+        METRICS.messages_remaining.store(self.message_rx.remaining() as u64, Ordering::Relaxed);
+        METRICS.firehose_seq.store(cursor.get(), Ordering::Relaxed);
 
         Ok(true)
     }
 
+    #[allow(unused_variables)]
+    fn handle_account(
+        &mut self, cursor: &mut Cursor, command: AccountCommand,
+    ) -> Result<(), ManagerError> {
+        let (did, status) = match command {
+            AccountCommand::Takedown { did, status } => {
+                self.conn.execute(
+                    "
+                        INSERT INTO takedowns (did, status, created)
+                        VALUES (?1, ?2, ?3)
+                        ON CONFLICT(did)
+                        DO UPDATE SET status = excluded.status, created = excluded.created
+                    ",
+                    (&did, &status, Utc::now()),
+                )?;
+                self.takedowns.insert(did.clone(), status.clone());
+                (did, Some(status))
+            }
+            AccountCommand::Restore { did } => {
+                if self.takedowns.remove(&did).is_none() {
+                    return Ok(());
+                }
+                self.conn.execute("DELETE FROM takedowns WHERE did = ?1", (&did,))?;
+                (did, None)
+            }
+        };
+        tracing::info!(%did, ?status, "updated account takedown");
+
+        // a relay-level takedown is announced to consumers like any upstream #account event
+        #[cfg(not(feature = "labeler"))]
+        self.emit_account(cursor, did, status)?;
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "labeler"))]
+    fn emit_account(
+        &self, cursor: &mut Cursor, did: String, status: Option<AccountStatus>,
+    ) -> Result<(), ManagerError> {
+        let event = SubscribeReposEvent::Account(SubscribeReposAccount {
+            seq: 0,
+            did,
+            time: Utc::now(),
+            active: status.is_none(),
+            status,
+        });
+        let data = event.serialize(256, cursor.next())?;
+        self.firehose.insert(*cursor, data)?;
+        METRICS.accepted("#account");
+        Ok(())
+    }
+
+    /// Marks a repo whose commits no longer chain as desynchronized, and fetches it again from
+    /// its pds.
+    #[cfg(not(feature = "labeler"))]
+    fn desync(&mut self, cursor: &mut Cursor, did: String, host: &str) -> Result<(), ManagerError> {
+        let now = Instant::now();
+        match self.desynced.entry(did) {
+            Entry::Occupied(mut entry) => {
+                if now.duration_since(*entry.get()) >= REPOS_RESYNC_RETRY {
+                    entry.insert(now);
+                    let did = entry.key().clone();
+                    self.request_resync(&did, true)?;
+                }
+            }
+            Entry::Vacant(entry) => {
+                let did = entry.key().clone();
+                tracing::info!(%did, %host, "repo desynchronized");
+                entry.insert(now);
+                self.desynced_dirty.insert(did.clone(), Some(host.to_owned()));
+                self.request_resync(&did, true)?;
+                self.emit_account(cursor, did, Some(AccountStatus::Desynchronized))?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "labeler"))]
+    fn request_resync(&mut self, did: &str, urgent: bool) -> Result<(), ManagerError> {
+        // still resolving, desynchronized repos are retried on their next dropped commit
+        match self.resolver.resolve(did)? {
+            // from the repo's own pds, the host might only be relaying it
+            Some((Some(pds), key)) => {
+                let pds = pds.to_owned();
+                let key = *key;
+                self.resyncer.request(did, &pds, key, urgent);
+            }
+            Some((None, _)) => tracing::debug!(%did, "no pds to resync from"),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Installs the state of a verified repo, relaying it as a `#sync` unless it's the state we
+    /// already had.
+    #[cfg(not(feature = "labeler"))]
+    fn resync(
+        &mut self, cursor: &mut Cursor, did: String, res: Result<Resynced, ResyncError>,
+    ) -> Result<(), ManagerError> {
+        let span = tracing::debug_span!("resync", %did);
+        let _enter = span.enter();
+        let resynced = match res {
+            Ok(resynced) => resynced,
+            Err(err) => {
+                tracing::info!(%err, "unable to resync repo");
+                return Ok(());
+            }
+        };
+        let Some(prev) = self.repos.get(&self.conn, &did)? else {
+            return Ok(());
+        };
+        // newer commits might have been relayed while fetching
+        if resynced.rev.older_than(&prev.rev) {
+            tracing::debug!(prev = %prev.rev, rev = %resynced.rev, "resynced repo is older");
+            return Ok(());
+        }
+        let desynced = self.desynced.contains_key(&did);
+        if prev.rev == resynced.rev && prev.data == resynced.data && !desynced {
+            tracing::debug!(rev = %resynced.rev, "verified repo");
+            return Ok(());
+        }
+
+        let data = resynced.event.serialize(256, cursor.next())?;
+        self.firehose.insert(*cursor, data)?;
+        METRICS.accepted("#sync");
+        self.repos.insert(
+            &self.conn,
+            did.clone(),
+            RepoState { rev: resynced.rev, data: resynced.data, head: resynced.head },
+        )?;
+        self.resynced(cursor, &did)
+    }
+
+    /// Reactivates a desynchronized repo once a `#sync` reset it.
+    #[cfg(not(feature = "labeler"))]
+    fn resynced(&mut self, cursor: &mut Cursor, did: &str) -> Result<(), ManagerError> {
+        if self.desynced.remove(did).is_none() {
+            return Ok(());
+        }
+        self.desynced_dirty.insert(did.to_owned(), None);
+        tracing::info!(%did, "repo resynchronized");
+        // inactive accounts keep their status
+        if self.takedowns.contains_key(did) || self.repos.inactive(&self.conn, did)?.is_some() {
+            return Ok(());
+        }
+        self.emit_account(cursor, did.to_owned(), None)
+    }
+
+    #[expect(clippy::too_many_lines)]
     fn scan_did(&mut self, cursor: &mut Cursor, did: &str) -> Result<(), ManagerError> {
         let Some((pds, key)) = self.resolver.resolve(did)? else { unreachable!("{did}") };
 
         let mut batch: Option<Batch> = None;
+        #[cfg(not(feature = "labeler"))]
+        let mut desyncs = Vec::new();
+        #[cfg(not(feature = "labeler"))]
+        let mut resyncs = Vec::new();
+        #[cfg(not(feature = "labeler"))]
+        let mut unverified = Vec::new();
         for res in self.queue.prefix(&did) {
             let (k, input) = res?;
-            batch.get_or_insert_with(|| DB.batch()).remove(&self.queue, k.clone());
+            batch.get_or_insert_with(|| self.db.batch()).remove(&self.queue, k.clone());
 
             #[expect(clippy::unwrap_used)]
             let host = std::str::from_utf8(&k).unwrap().split('>').nth(1).unwrap();
@@ -335,6 +976,36 @@ impl Manager {
             let did = event.did();
             let span = tracing::debug_span!("msg_data", type = %type_, %seq, %time, %did);
             let _enter = span.enter();
+            if self.takedowns.contains_key(did) {
+                tracing::debug!("dropping queued event for taken down account");
+                continue;
+            }
+            #[cfg(not(feature = "labeler"))]
+            if let SubscribeReposEvent::Account(account) = &event {
+                if pds != Some(host) {
+                    tracing::trace!(?pds, "hostname pds mismatch");
+                    self.hosts
+                        .entry_ref(host)
+                        .or_default()
+                        .reject(type_, ValidationError::HostMismatch);
+                    continue;
+                }
+                self.repos.set_active(
+                    &self.conn,
+                    did.to_owned(),
+                    account.active,
+                    account.status.clone(),
+                )?;
+                let data = event.serialize(input.len(), cursor.next())?;
+                self.firehose.insert(*cursor, data)?;
+                METRICS.accepted(type_);
+                continue;
+            }
+            #[cfg(not(feature = "labeler"))]
+            if let Some(status) = self.repos.inactive(&self.conn, did)? {
+                tracing::debug!(?status, "dropping queued event for inactive account");
+                continue;
+            }
 
             #[allow(unused_variables)]
             #[expect(clippy::unwrap_used)]
@@ -348,87 +1019,131 @@ impl Manager {
 
             if let Some(pds) = pds {
                 if host != pds {
-                    tracing::debug!(%pds, "hostname pds mismatch");
+                    tracing::trace!(%pds, "hostname pds mismatch");
+                    self.hosts
+                        .entry_ref(host)
+                        .or_default()
+                        .reject(type_, ValidationError::HostMismatch);
                     continue;
                 }
             }
 
             // verify signature
             #[allow(clippy::needless_borrow)]
-            match utils::verify_commit_sig(&commit, key) {
-                Ok(valid) => {
-                    if !valid {
-                        tracing::debug!(?key, "signature mismatch");
-                        continue;
-                    }
-                }
-                Err(err) => {
-                    tracing::debug!(%err, ?key, "signature check error");
-                    continue;
-                }
+            if let Err(err) = utils::verify_commit_sig(&commit, key) {
+                tracing::trace!(?key, "signature check failed");
+                self.hosts.entry_ref(host).or_default().reject(type_, err);
+                continue;
+            }
+
+            #[cfg(not(feature = "labeler"))]
+            if self.desynced.contains_key(did) && matches!(event, SubscribeReposEvent::Commit(_)) {
+                tracing::debug!("dropping queued commit for desynchronized repo");
+                desyncs.push((did.to_owned(), host.to_owned()));
+                continue;
             }
 
             // verify commit message
             #[cfg(not(feature = "labeler"))]
-            let (rev, data, entry) = { (commit.rev, commit.data, self.repos.entry(commit.did)) };
+            let (rev, data, repo) = (commit.rev, commit.data, commit.did);
             #[cfg(not(feature = "labeler"))]
-            if let SubscribeReposEvent::Commit(commit) = &event {
-                // TODO: should still validate records existing in blocks, etc
-                if let Entry::Occupied(prev) = &entry {
-                    let prev = prev.get();
-                    let span = tracing::debug_span!("previous", rev = %prev.rev, data = %prev.data, head = %prev.head);
-                    let _enter = span.enter();
-                    if !utils::verify_commit_event(commit, data, prev) {
-                        continue;
+            let prev = self.repos.get(&self.conn, &repo)?;
+            #[cfg(not(feature = "labeler"))]
+            let known = prev.is_some();
+            #[cfg(not(feature = "labeler"))]
+            if !known {
+                let limit =
+                    self.account_limits.get(host).copied().unwrap_or(self.host_max_accounts);
+                let state = self.hosts.entry_ref(host).or_default();
+                if state.accounts >= limit {
+                    state.reject(type_, ValidationError::AccountLimit);
+                    continue;
+                }
+            }
+            // a #sync replaces the repo state, so the next #commit chains from it
+            #[cfg(not(feature = "labeler"))]
+            if let Some(prev) = prev {
+                let span = tracing::debug_span!("previous", rev = %prev.rev, data = %prev.data, head = %prev.head);
+                let _enter = span.enter();
+                let res = match &event {
+                    // TODO: should still validate records existing in blocks, etc
+                    SubscribeReposEvent::Commit(commit) => {
+                        // dropped even in lenient mode, the repo is resynced instead
+                        if utils::breaks_chain(commit, prev) {
+                            desyncs.push((did.to_owned(), host.to_owned()));
+                            Err(ValidationError::ChainBreak)
+                        } else {
+                            utils::verify_commit_event(commit, data, prev, self.strict_mst)
+                        }
                     }
+                    SubscribeReposEvent::Sync(_) => utils::verify_sync_event(&rev, prev),
+                    _ => Ok(()),
+                };
+                if let Err(err) = res {
+                    self.hosts.entry_ref(host).or_default().reject(type_, err);
+                    continue;
                 }
             }
+            #[cfg(not(feature = "labeler"))]
+            if matches!(event, SubscribeReposEvent::Sync(_)) && self.desynced.contains_key(did) {
+                resyncs.push(did.to_owned());
+            }
 
             let msg = event.serialize(input.len(), cursor.next())?;
             self.firehose.insert(*cursor, msg)?;
+            METRICS.accepted(type_);
             #[cfg(not(feature = "labeler"))]
-            entry.insert(RepoState { rev, data, head });
+            if !known {
+                self.hosts.entry_ref(host).or_default().accounts += 1;
+                if self.resync_new_repos {
+                    unverified.push(repo.clone());
+                }
+            }
+            #[cfg(not(feature = "labeler"))]
+            self.repos.insert(&self.conn, repo, RepoState { rev, data, head })?;
         }
         if let Some(batch) = batch {
             batch.commit()?;
         }
+        #[cfg(not(feature = "labeler"))]
+        {
+            for (did, host) in desyncs {
+                self.desync(cursor, did, &host)?;
+            }
+            for did in resyncs {
+                self.resynced(cursor, &did)?;
+            }
+            for did in unverified {
+                self.request_resync(&did, false)?;
+            }
+        }
 
         Ok(())
     }
 }
 
+// sqlite has no `ADD COLUMN IF NOT EXISTS`, so tolerate the column already being there
+fn add_column(conn: &Connection, table: &str, column: &str) -> Result<(), rusqlite::Error> {
+    match conn.execute(&format!("ALTER TABLE {table} ADD COLUMN {column}"), ()) {
+        Ok(_) => Ok(()),
+        Err(rusqlite::Error::SqliteFailure(_, Some(msg)))
+            if msg.starts_with("duplicate column name") =>
+        {
+            Ok(())
+        }
+        Err(err) => Err(err),
+    }
+}
+
 impl Drop for Manager {
     fn drop(&mut self) {
         SHUTDOWN.store(true, Ordering::Relaxed);
 
         if let Err(err) = self.persist() {
-            tracing::warn!(%err, "unable to persist host state\n{:#?}", self.hosts);
-        }
-
-        #[cfg(not(feature = "labeler"))]
-        match DB.open_partition("repos", PartitionCreateOptions::default()) {
-            Ok(repos) => {
-                let len = self.repos.len();
-                let mut batch = Batch::with_capacity(DB.clone(), len);
-                for (did, state) in self.repos.drain() {
-                    #[expect(clippy::unwrap_used)]
-                    batch.insert(
-                        &repos,
-                        did.into_bytes(),
-                        serde_ipld_dagcbor::to_vec(&state).unwrap(),
-                    );
-                }
-                tracing::info!(%len, "persisting repos");
-                if let Err(err) = batch.commit() {
-                    tracing::warn!(%err, "unable to persist repo state");
-                }
-            }
-            Err(err) => {
-                tracing::warn!(%err, "unable to open repos tree");
-            }
+            tracing::warn!(%err, "unable to persist state\n{:#?}", self.hosts);
         }
 
-        if let Err(err) = DB.persist(PersistMode::SyncAll) {
+        if let Err(err) = self.db.persist(PersistMode::SyncAll) {
             tracing::warn!(%err, "unable to flush db");
         }
     }
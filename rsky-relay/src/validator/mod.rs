@@ -1,8 +1,13 @@
 mod event;
 mod manager;
+#[cfg(not(feature = "labeler"))]
+mod repos;
 mod resolver;
 #[cfg(not(feature = "labeler"))]
+mod resync;
+#[cfg(not(feature = "labeler"))]
 mod types;
 mod utils;
 
-pub use manager::{Manager, ManagerError};
+pub use event::{AccountStatus, Header};
+pub use manager::{AccountCommand, AccountSender, Manager, ManagerError};
@@ -0,0 +1,215 @@
+use std::num::NonZeroUsize;
+
+use cid::Cid;
+use hashbrown::HashMap;
+use lru::LruCache;
+use rusqlite::types::Type;
+use rusqlite::{Connection, OptionalExtension};
+
+use rsky_common::tid::TID;
+
+use crate::config::Config;
+use crate::validator::event::AccountStatus;
+use crate::validator::types::RepoState;
+
+/// What the relay knows of a repo: the last commit it relayed and the status of its account.
+struct Repo {
+    state: Option<RepoState>,
+    active: bool,
+    /// why the account is inactive, if its pds gave a reason
+    status: Option<AccountStatus>,
+}
+
+impl Default for Repo {
+    fn default() -> Self {
+        Self { state: None, active: true, status: None }
+    }
+}
+
+/// Repo states and account statuses kept in the `repos` table of `relay.db`, behind a bounded
+/// cache of recently seen repos.
+///
+/// Updates are buffered until the next `flush`, which the validator runs along with the hosts
+/// every few seconds, so a crash only loses the states written since.
+pub struct RepoStore {
+    cache: LruCache<String, Repo>,
+    /// updates not yet written to sqlite, they take precedence over the cache
+    dirty: HashMap<String, Repo>,
+}
+
+impl RepoStore {
+    pub fn new(config: &Config, conn: &Connection) -> Result<Self, rusqlite::Error> {
+        // accounts can have a status before their first commit, hence the nullable state
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS repos (
+                did TEXT PRIMARY KEY,
+                rev TEXT,
+                data TEXT,
+                head TEXT,
+                active INTEGER NOT NULL DEFAULT 1,
+                status TEXT
+            ) WITHOUT ROWID",
+            (),
+        )?;
+        let cache =
+            LruCache::new(NonZeroUsize::new(config.capacity_repos).unwrap_or(NonZeroUsize::MIN));
+        Ok(Self { cache, dirty: HashMap::new() })
+    }
+
+    pub fn get(
+        &mut self, conn: &Connection, did: &str,
+    ) -> Result<Option<&RepoState>, rusqlite::Error> {
+        Ok(self.repo(conn, did)?.and_then(|repo| repo.state.as_ref()))
+    }
+
+    /// Returns the account's status while it's inactive, `Some(None)` if no reason was given.
+    pub fn inactive(
+        &mut self, conn: &Connection, did: &str,
+    ) -> Result<Option<Option<AccountStatus>>, rusqlite::Error> {
+        Ok(self.repo(conn, did)?.filter(|repo| !repo.active).map(|repo| repo.status.clone()))
+    }
+
+    pub fn insert(
+        &mut self, conn: &Connection, did: String, state: RepoState,
+    ) -> Result<(), rusqlite::Error> {
+        self.repo_mut(conn, did)?.state = Some(state);
+        Ok(())
+    }
+
+    pub fn set_active(
+        &mut self, conn: &Connection, did: String, active: bool, status: Option<AccountStatus>,
+    ) -> Result<(), rusqlite::Error> {
+        let repo = self.repo_mut(conn, did)?;
+        repo.active = active;
+        repo.status = if active { None } else { status };
+        Ok(())
+    }
+
+    /// Writes the buffered updates in a single transaction, moving them to the cache.
+    pub fn flush(&mut self, conn: &mut Connection) -> Result<(), rusqlite::Error> {
+        if self.dirty.is_empty() {
+            return Ok(());
+        }
+        let tx = conn.transaction()?;
+        let mut stmt = tx.prepare_cached(
+            "
+                INSERT INTO repos (did, rev, data, head, active, status)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                ON CONFLICT(did)
+                DO UPDATE SET rev = excluded.rev, data = excluded.data, head = excluded.head,
+                              active = excluded.active, status = excluded.status
+            ",
+        )?;
+        for (did, repo) in &self.dirty {
+            let state = repo.state.as_ref();
+            stmt.execute((
+                did,
+                state.map(|state| &state.rev.0),
+                state.map(|state| state.data.to_string()),
+                state.map(|state| state.head.to_string()),
+                repo.active,
+                &repo.status,
+            ))?;
+        }
+        drop(stmt);
+        tx.commit()?;
+        for (did, repo) in self.dirty.drain() {
+            self.cache.put(did, repo);
+        }
+        Ok(())
+    }
+
+    fn repo(&mut self, conn: &Connection, did: &str) -> Result<Option<&Repo>, rusqlite::Error> {
+        if self.dirty.contains_key(did) {
+            return Ok(self.dirty.get(did));
+        }
+        if self.cache.contains(did) {
+            return Ok(self.cache.get(did));
+        }
+        let Some(repo) = query(conn, did)? else {
+            return Ok(None);
+        };
+        Ok(Some(self.cache.get_or_insert(did.to_owned(), || repo)))
+    }
+
+    // loads the row before buffering the update, so the flush keeps the columns it doesn't touch
+    fn repo_mut(&mut self, conn: &Connection, did: String) -> Result<&mut Repo, rusqlite::Error> {
+        let repo = if self.dirty.contains_key(&did) {
+            None
+        } else if let Some(repo) = self.cache.pop(&did) {
+            Some(repo)
+        } else {
+            query(conn, &did)?
+        };
+        Ok(self.dirty.entry(did).or_insert_with(|| repo.unwrap_or_default()))
+    }
+}
+
+fn query(conn: &Connection, did: &str) -> Result<Option<Repo>, rusqlite::Error> {
+    let mut stmt =
+        conn.prepare_cached("SELECT rev, data, head, active, status FROM repos WHERE did = ?1")?;
+    stmt.query_row((did,), |row| {
+        let (rev, data, head): (Option<String>, Option<String>, Option<String>) =
+            (row.get(0)?, row.get(1)?, row.get(2)?);
+        let state = match (rev, data, head) {
+            (Some(rev), Some(data), Some(head)) => Some(RepoState {
+                rev: TID(rev),
+                data: parse_cid(&data, 1)?,
+                head: parse_cid(&head, 2)?,
+            }),
+            _ => None,
+        };
+        Ok(Repo { state, active: row.get(3)?, status: row.get(4)? })
+    })
+    .optional()
+}
+
+fn parse_cid(cid: &str, idx: usize) -> Result<Cid, rusqlite::Error> {
+    Cid::try_from(cid)
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, Box::new(err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::Cid;
+    use rusqlite::Connection;
+
+    use rsky_common::tid::TID;
+
+    use crate::config::Config;
+    use crate::validator::event::AccountStatus;
+    use crate::validator::repos::RepoStore;
+    use crate::validator::types::RepoState;
+
+    const DID: &str = "did:plc:ar7c4by46qjdydhdevvrndac";
+
+    fn state() -> RepoState {
+        #[expect(clippy::unwrap_used)]
+        let cid =
+            Cid::try_from("bafyreiapddjgxnyaogx2gvakuawukls5rr2hdwbkrjb4nwjffwpkb4734m").unwrap();
+        RepoState { rev: TID("3lr4pmliavk2l".to_owned()), data: cid, head: cid }
+    }
+
+    #[test]
+    #[expect(clippy::unwrap_used)]
+    fn status_survives_commits() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let mut repos = RepoStore::new(&Config::default(), &conn).unwrap();
+        // an account can be deactivated before relaying any commit
+        repos.set_active(&conn, DID.to_owned(), false, Some(AccountStatus::Deactivated)).unwrap();
+        repos.flush(&mut conn).unwrap();
+        repos.insert(&conn, DID.to_owned(), state()).unwrap();
+        repos.flush(&mut conn).unwrap();
+
+        // read back from sqlite, not the cache
+        let mut repos = RepoStore::new(&Config::default(), &conn).unwrap();
+        assert_eq!(repos.inactive(&conn, DID).unwrap(), Some(Some(AccountStatus::Deactivated)));
+        assert_eq!(repos.get(&conn, DID).unwrap().map(|state| &state.rev.0), Some(&state().rev.0));
+
+        repos.set_active(&conn, DID.to_owned(), true, None).unwrap();
+        repos.flush(&mut conn).unwrap();
+        let mut repos = RepoStore::new(&Config::default(), &conn).unwrap();
+        assert_eq!(repos.inactive(&conn, DID).unwrap(), None);
+        assert!(repos.get(&conn, DID).unwrap().is_some());
+    }
+}
@@ -1,6 +1,7 @@
 use std::io::BufRead;
 use std::num::NonZeroUsize;
 use std::pin::Pin;
+use std::sync::atomic::Ordering;
 use std::time::{Duration, Instant};
 
 use bytes::{Buf, Bytes};
@@ -18,7 +19,8 @@ use tokio::time::timeout;
 
 use rsky_identity::types::DidDocument;
 
-use crate::config::{CAPACITY_CACHE, DO_PLC_EXPORT, PLC_EXPORT_INTERVAL};
+use crate::config::Config;
+use crate::metrics::METRICS;
 use crate::validator::event::{DidEndpoint, DidKey};
 
 const POLL_TIMEOUT: Duration = Duration::from_micros(10);
@@ -50,6 +52,8 @@ pub enum ResolverError {
 pub struct Resolver {
     cache: LruCache<String, (DidEndpoint, DidKey)>,
     conn: Connection,
+    plc_export: bool,
+    plc_export_interval: Duration,
     last: Instant,
     after: Option<String>,
     client: Client,
@@ -58,19 +62,21 @@ pub struct Resolver {
 }
 
 impl Resolver {
-    pub fn new() -> Result<Self, ResolverError> {
-        #[expect(clippy::unwrap_used)]
-        let cache = LruCache::new(NonZeroUsize::new(CAPACITY_CACHE).unwrap());
-        let flag = if *DO_PLC_EXPORT {
+    pub fn new(config: &Config) -> Result<Self, ResolverError> {
+        let cache =
+            LruCache::new(NonZeroUsize::new(config.capacity_cache).unwrap_or(NonZeroUsize::MIN));
+        let plc_export = config.plc_export && !cfg!(feature = "labeler");
+        let plc_export_interval = config.plc_export_interval();
+        let flag = if plc_export {
             OpenFlags::SQLITE_OPEN_READ_WRITE
         } else {
             OpenFlags::SQLITE_OPEN_READ_ONLY
         };
         let conn = Connection::open_with_flags(
-            "plc_directory.db",
+            &config.plc_directory_db,
             flag | OpenFlags::SQLITE_OPEN_NO_MUTEX,
         )?;
-        if *DO_PLC_EXPORT {
+        if plc_export {
             match conn.execute("PRAGMA secure_delete = OFF", []) {
                 Ok(_) | Err(rusqlite::Error::ExecuteReturnedResults) => {}
                 Err(err) => Err(err)?,
@@ -80,7 +86,7 @@ impl Resolver {
             conn.execute("PRAGMA optimize = 0x10002", [])?;
         }
         let now = Instant::now();
-        let last = now.checked_sub(PLC_EXPORT_INTERVAL).unwrap_or(now);
+        let last = now.checked_sub(plc_export_interval).unwrap_or(now);
         let after = conn.query_one(
             "SELECT created_at FROM plc_operations ORDER BY created_at DESC LIMIT 1",
             [],
@@ -94,7 +100,17 @@ impl Resolver {
             .build()?;
         let inflight = HashSet::new();
         let futures = FuturesUnordered::new();
-        Ok(Self { cache, conn, last, after, client, inflight, futures })
+        Ok(Self {
+            cache,
+            conn,
+            plc_export,
+            plc_export_interval,
+            last,
+            after,
+            client,
+            inflight,
+            futures,
+        })
     }
 
     pub fn expire(&mut self, did: &str, time: DateTime<Utc>) {
@@ -113,13 +129,21 @@ impl Resolver {
             return Ok(None);
         }
         // if let Some(_) = self.cache.get(did) doesn't work because of NLL
-        if self.cache.get(did).is_some() || self.query_db(did)? {
+        let hit = self.cache.get(did).is_some();
+        let counter =
+            if hit { &METRICS.resolver_cache_hits } else { &METRICS.resolver_cache_misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        if hit || self.query_db(did)? {
             return Ok(self.cache.peek_mru().map(|(_, v)| (v.0.as_ref().map(AsRef::as_ref), &v.1)));
         }
         self.request(did);
         Ok(None)
     }
 
+    pub fn inflight(&self) -> usize {
+        self.inflight.len()
+    }
+
     pub fn query_db(&mut self, did: &str) -> Result<bool, ResolverError> {
         let mut stmt = self.conn.prepare_cached("SELECT * FROM plc_keys WHERE did = ?1")?;
         match stmt.query_one([did], |row| {
@@ -147,7 +171,7 @@ impl Resolver {
     pub fn request(&mut self, did: &str) {
         self.inflight.insert(did.to_owned());
         if let Some(plc) = did.strip_prefix("did:plc:") {
-            let plc = if *DO_PLC_EXPORT { None } else { Some(plc) };
+            let plc = if self.plc_export { None } else { Some(plc) };
             self.send_req(None, plc);
         } else if let Some(web) = did.strip_prefix("did:web:") {
             let Ok(web) = urlencoding::decode(web) else {
@@ -240,7 +264,7 @@ impl Resolver {
                     tracing::debug!(%err, "fetch error");
                 }
             }
-        } else if *DO_PLC_EXPORT && self.last.elapsed() > PLC_EXPORT_INTERVAL {
+        } else if self.plc_export && self.last.elapsed() > self.plc_export_interval {
             self.send_req(None, None);
         }
         Ok(Vec::new())
@@ -0,0 +1,274 @@
+use std::collections::{TryReserveError, VecDeque};
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use cid::Cid;
+use futures::StreamExt;
+use futures::stream::FuturesUnordered;
+use hashbrown::{HashMap, HashSet};
+use reqwest::Client;
+use rs_car_sync::CarReader;
+use serde::Serialize;
+use thiserror::Error;
+use tokio::task::JoinError;
+use tokio::time::timeout;
+
+use rsky_common::tid::TID;
+
+use crate::config::{Config, REPOS_RESYNC_INTERVAL, REPOS_RESYNC_MAX, REPOS_RESYNC_QUEUE};
+use crate::validator::event::{
+    Commit, DidKey, ParseError, SubscribeReposEvent, SubscribeReposSync,
+};
+use crate::validator::types::{BlockMap, InvertError, MAX_TREE_DEPTH, Node, NodeEntry};
+use crate::validator::utils::{self, ValidationError};
+
+const POLL_TIMEOUT: Duration = Duration::from_micros(10);
+const REQ_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const MAX_REPO_BYTES: usize = 256 * 1024 * 1024;
+
+type ResyncFuture =
+    Pin<Box<dyn Future<Output = (String, String, Result<Resynced, ResyncError>)> + Send>>;
+
+#[derive(Debug, Error)]
+pub enum ResyncError {
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("parse error: {0}")]
+    Parse(#[from] ParseError),
+    #[error("encode error: {0}")]
+    Encode(#[from] serde_ipld_dagcbor::EncodeError<TryReserveError>),
+    #[error("validation error: {0}")]
+    Validation(#[from] ValidationError),
+    #[error("invert error: {0}")]
+    Invert(#[from] InvertError),
+    #[error("join error: {0}")]
+    Join(#[from] JoinError),
+    #[error("repo too big")]
+    TooBig,
+    #[error("missing block: {0}")]
+    MissingBlock(Cid),
+    #[error("partial tree")]
+    PartialTree,
+    #[error("tree root mismatch: {0}")]
+    RootMismatch(Cid),
+    #[error("malformed car")]
+    MalformedCar,
+}
+
+/// A repo checked in full against its signed commit, ready to be relayed as a `#sync`.
+#[derive(Debug)]
+pub struct Resynced {
+    /// `#sync` whose blocks only hold the commit
+    pub event: SubscribeReposEvent,
+    pub rev: TID,
+    pub data: Cid,
+    pub head: Cid,
+}
+
+#[derive(Debug, Serialize)]
+struct CarHeader {
+    roots: Vec<Cid>,
+    version: u64,
+}
+
+#[derive(Debug, Default)]
+struct HostQueue {
+    pending: VecDeque<(String, DidKey)>,
+    busy: bool,
+    next: Option<Instant>,
+}
+
+/// Fetches repos from their PDS with `com.atproto.sync.getRepo` and verifies them: the commit
+/// signature against the DID key, and the whole MST against the commit's root.
+///
+/// Each PDS serves one `getRepo` at a time, `REPOS_RESYNC_INTERVAL` apart, and the CAR is
+/// verified on the blocking pool, away from the validator's loop.
+pub struct Resyncer {
+    client: Client,
+    scheme: &'static str,
+    max_clock_skew: Duration,
+    /// dids queued or being fetched
+    inflight: HashSet<String>,
+    hosts: HashMap<String, HostQueue>,
+    queued: usize,
+    futures: FuturesUnordered<ResyncFuture>,
+}
+
+impl Resyncer {
+    pub fn new(config: &Config) -> Result<Self, ResyncError> {
+        let client = Client::builder()
+            .user_agent("rsky-relay")
+            .timeout(REQ_TIMEOUT)
+            .https_only(!config.dev_mode)
+            .build()?;
+        let scheme = if config.dev_mode { "http" } else { "https" };
+        Ok(Self {
+            client,
+            scheme,
+            max_clock_skew: config.max_clock_skew(),
+            inflight: HashSet::new(),
+            hosts: HashMap::new(),
+            queued: 0,
+            futures: FuturesUnordered::new(),
+        })
+    }
+
+    /// Queues a repo, `urgent` ones (eg desynchronized) going ahead of the host's other repos.
+    pub fn request(&mut self, did: &str, pds: &str, key: DidKey, urgent: bool) {
+        if self.inflight.contains(did) {
+            return;
+        }
+        let queue = self.hosts.entry_ref(pds).or_default();
+        if queue.pending.len() >= REPOS_RESYNC_QUEUE {
+            tracing::debug!(%did, %pds, "resync queue full");
+            return;
+        }
+        tracing::debug!(%did, %pds, %urgent, "queueing resync");
+        if urgent {
+            queue.pending.push_front((did.to_owned(), key));
+        } else {
+            queue.pending.push_back((did.to_owned(), key));
+        }
+        self.inflight.insert(did.to_owned());
+        self.queued += 1;
+    }
+
+    pub async fn poll(&mut self) -> Option<(String, Result<Resynced, ResyncError>)> {
+        self.start();
+        let (did, pds, res) = timeout(POLL_TIMEOUT, self.futures.next()).await.ok()??;
+        self.inflight.remove(&did);
+        if let Some(queue) = self.hosts.get_mut(&pds) {
+            queue.busy = false;
+            queue.next = Some(Instant::now() + REPOS_RESYNC_INTERVAL);
+        }
+        Some((did, res))
+    }
+
+    fn start(&mut self) {
+        if self.queued == 0 || self.futures.len() >= REPOS_RESYNC_MAX {
+            return;
+        }
+        let now = Instant::now();
+        for (pds, queue) in &mut self.hosts {
+            if self.futures.len() >= REPOS_RESYNC_MAX {
+                break;
+            }
+            if queue.busy || queue.next.is_some_and(|next| next > now) {
+                continue;
+            }
+            let Some((did, key)) = queue.pending.pop_front() else {
+                continue;
+            };
+            queue.busy = true;
+            self.queued -= 1;
+            let client = self.client.clone();
+            let url = format!("{}://{pds}/xrpc/com.atproto.sync.getRepo", self.scheme);
+            let pds = pds.clone();
+            let max_clock_skew = self.max_clock_skew;
+            self.futures.push(Box::pin(async move {
+                let res = fetch(&client, &url, did.clone(), key, max_clock_skew).await;
+                (did, pds, res)
+            }));
+        }
+        self.hosts.retain(|_, queue| {
+            queue.busy || !queue.pending.is_empty() || queue.next.is_some_and(|next| next > now)
+        });
+    }
+}
+
+async fn fetch(
+    client: &Client, url: &str, did: String, key: DidKey, max_clock_skew: Duration,
+) -> Result<Resynced, ResyncError> {
+    let mut res = client.get(url).query(&[("did", &did)]).send().await?.error_for_status()?;
+    let mut car = Vec::new();
+    while let Some(chunk) = res.chunk().await? {
+        if car.len() + chunk.len() > MAX_REPO_BYTES {
+            return Err(ResyncError::TooBig);
+        }
+        car.extend_from_slice(&chunk);
+    }
+    tokio::task::spawn_blocking(move || verify(did, &key, &car, max_clock_skew)).await?
+}
+
+fn verify(
+    did: String, key: &DidKey, mut car: &[u8], max_clock_skew: Duration,
+) -> Result<Resynced, ResyncError> {
+    let reader = CarReader::new(&mut car, true).map_err(ParseError::from)?;
+    let head = reader.header.roots.first().copied().ok_or(ResyncError::MalformedCar)?;
+    let mut blocks = BlockMap::new();
+    for next in reader {
+        let (cid, block) = next.map_err(ParseError::from)?;
+        blocks.insert(cid, block);
+    }
+
+    let block = blocks.get(&head).ok_or(ResyncError::MissingBlock(head))?;
+    let commit: Commit = serde_ipld_dagcbor::from_slice(block).map_err(ParseError::from)?;
+    let event = SubscribeReposEvent::Sync(SubscribeReposSync {
+        seq: 0,
+        did,
+        blocks: write_car(&head, &[(head, block)])?,
+        rev: commit.rev.clone(),
+        time: Utc::now(),
+    });
+    event.validate(&commit, &head, max_clock_skew)?;
+    utils::verify_commit_sig(&commit, key)?;
+
+    let mut tree =
+        Node::load(&blocks, commit.data)?.ok_or(ResyncError::MissingBlock(commit.data))?;
+    check_tree(&mut tree, &blocks, 0)?;
+    let root = tree.root()?;
+    if root != commit.data {
+        return Err(ResyncError::RootMismatch(root));
+    }
+
+    Ok(Resynced { event, rev: commit.rev, data: commit.data, head })
+}
+
+// every node and record must be in the CAR, and nodes are marked dirty so the root is recomputed
+fn check_tree(node: &mut Node, blocks: &BlockMap, depth: usize) -> Result<(), ResyncError> {
+    if depth > MAX_TREE_DEPTH {
+        return Err(ParseError::TooDeep.into());
+    }
+    node.dirty = true;
+    for entry in &mut node.entries {
+        match entry {
+            NodeEntry::Value { value, .. } => {
+                if !blocks.contains_key(value) {
+                    return Err(ResyncError::MissingBlock(*value));
+                }
+            }
+            NodeEntry::Child { child: Some(child), dirty, .. } => {
+                *dirty = true;
+                check_tree(child, blocks, depth + 1)?;
+            }
+            NodeEntry::Child { child: None, .. } => return Err(ResyncError::PartialTree),
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn write_car(root: &Cid, blocks: &[(Cid, &[u8])]) -> Result<Vec<u8>, ResyncError> {
+    let header = serde_ipld_dagcbor::to_vec(&CarHeader { roots: vec![*root], version: 1 })?;
+    let len = blocks.iter().map(|(_, block)| block.len() + 48).sum::<usize>();
+    let mut car = Vec::with_capacity(header.len() + len + 4);
+    write_varint(&mut car, header.len());
+    car.extend_from_slice(&header);
+    for (cid, block) in blocks {
+        let cid = cid.to_bytes();
+        write_varint(&mut car, cid.len() + block.len());
+        car.extend_from_slice(&cid);
+        car.extend_from_slice(block);
+    }
+    Ok(car)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        #[expect(clippy::cast_possible_truncation)]
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    #[expect(clippy::cast_possible_truncation)]
+    out.push(n as u8);
+}
@@ -1,4 +1,5 @@
 use std::cmp::Ordering;
+use std::time::Duration;
 use std::{io, mem};
 
 use cid::multihash::{Code, Hasher, MultihashDigest};
@@ -16,11 +17,14 @@ use rsky_common::tid::TID;
 use crate::validator::event::{
     Commit, ParseError, SubscribeReposCommit, SubscribeReposCommitOperation, SubscribeReposEvent,
 };
+use crate::validator::utils::{self, ValidationError};
 
-// const FUTURE_REV_MAX: Duration = Duration::from_secs(60 * 5);
 const MAX_BLOCKS_BYTES: usize = 2_000_000;
 const MAX_COMMIT_OPS: usize = 200;
 const ATPROTO_REPO_VERSION: u8 = 3;
+// keys are placed by the leading zeros of their sha256, two bits per layer, so valid trees never
+// go below this depth
+pub const MAX_TREE_DEPTH: usize = 128;
 
 pub type BlockMap = HashMap<Cid, Vec<u8>>;
 
@@ -32,63 +36,68 @@ pub struct RepoState {
 }
 
 impl SubscribeReposEvent {
-    pub fn validate(&self, commit: &Commit, head: &Cid) -> bool {
+    pub fn validate(
+        &self, commit: &Commit, head: &Cid, max_clock_skew: Duration,
+    ) -> Result<(), ValidationError> {
         let rev = match &self {
             Self::Commit(commit) => {
                 if commit.too_big {
-                    tracing::debug!("deprecated tooBig commit flag set");
-                    return false;
+                    return Err(ValidationError::TooBig);
                 }
                 if commit.rebase {
-                    tracing::debug!("deprecated rebase commit flag set");
-                    return false;
+                    return Err(ValidationError::Rebase);
                 }
                 if &commit.commit != head {
                     tracing::debug!(inner = %commit.commit, "mismatched inner commit cid");
-                    return false;
+                    return Err(ValidationError::CommitCidMismatch);
                 }
                 if commit.ops.len() > MAX_COMMIT_OPS {
                     tracing::debug!(len = %commit.ops.len(), "too many ops in commit");
-                    return false;
+                    return Err(ValidationError::TooManyOps);
                 }
                 if commit.blocks.is_empty() {
-                    tracing::debug!("commit messaging missing blocks");
-                    return false;
+                    return Err(ValidationError::MissingBlocks);
                 }
                 if commit.blocks.len() > MAX_BLOCKS_BYTES {
-                    tracing::debug!(
-                        "blocks size ({} bytes) exceeds protocol limit",
-                        commit.blocks.len()
-                    );
-                    return false;
+                    tracing::debug!(len = %commit.blocks.len(), "blocks size exceeds protocol limit");
+                    return Err(ValidationError::BlocksTooBig);
                 }
                 &commit.rev
             }
             Self::Sync(sync) => {
                 if sync.blocks.len() > MAX_BLOCKS_BYTES {
-                    tracing::debug!(
-                        "blocks size ({} bytes) exceeds protocol limit",
-                        sync.blocks.len()
-                    );
-                    return false;
+                    tracing::debug!(len = %sync.blocks.len(), "blocks size exceeds protocol limit");
+                    return Err(ValidationError::BlocksTooBig);
+                }
+                // the signed commit is all a sync carries
+                let mut blocks = sync.blocks.as_slice();
+                let only_commit = CarReader::new(&mut blocks, true).is_ok_and(|reader| {
+                    let root = reader.header.roots.first().copied();
+                    let mut cids = reader.map(|next| next.map(|(cid, _)| cid));
+                    root == Some(*head)
+                        && matches!((cids.next(), cids.next()), (Some(Ok(cid)), None) if cid == *head)
+                });
+                if !only_commit {
+                    return Err(ValidationError::SyncBlocks);
                 }
                 &sync.rev
             }
-            _ => return true,
+            _ => return Ok(()),
         };
         if commit.did != self.did() {
             tracing::debug!(inner = %commit.did, "mismatched inner commit did");
-            return false;
+            return Err(ValidationError::DidMismatch);
         }
         if &commit.rev != rev {
             tracing::debug!(inner = %rev, "mismatched inner commit rev");
-            return false;
+            return Err(ValidationError::RevMismatch);
         }
+        utils::verify_rev(rev, max_clock_skew)?;
         if commit.version != ATPROTO_REPO_VERSION {
             tracing::debug!(version = %commit.version, "unsupported repo version");
-            return false;
+            return Err(ValidationError::UnsupportedVersion);
         }
-        true
+        Ok(())
     }
 
     pub fn commit(&self) -> Result<Option<(Commit, Cid)>, ParseError> {
@@ -765,7 +774,14 @@ impl Node {
         idx
     }
 
-    fn load(block_map: &BlockMap, cid: Cid) -> Result<Option<Self>, ParseError> {
+    pub fn load(block_map: &BlockMap, cid: Cid) -> Result<Option<Self>, ParseError> {
+        Self::load_at(block_map, cid, 0)
+    }
+
+    fn load_at(block_map: &BlockMap, cid: Cid, depth: usize) -> Result<Option<Self>, ParseError> {
+        if depth > MAX_TREE_DEPTH {
+            return Err(ParseError::TooDeep);
+        }
         let Some(block) = block_map.get(&cid) else {
             // allow "partial" trees
             return Ok(None);
@@ -775,7 +791,7 @@ impl Node {
         let mut n = nd.into_node(cid);
         for entry in &mut n.entries {
             if let NodeEntry::Child { cid: Some(cid), child: node, .. } = entry {
-                if let Some(child) = Self::load(block_map, *cid)? {
+                if let Some(child) = Self::load_at(block_map, *cid, depth + 1)? {
                     // NOTE: this is kind of a hack
                     if n.height < 0 && child.height >= 0 {
                         n.height = child.height + 1;
@@ -949,3 +965,104 @@ mod ipld_bytes {
         Ok(key_suffix)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+    use cid::Cid;
+    use cid::multihash::{Code, MultihashDigest};
+    use ipld_core::codec::Codec;
+    use serde::Serialize;
+    use serde_ipld_dagcbor::codec::DagCborCodec;
+
+    use rsky_common::tid::TID;
+
+    use crate::validator::event::{Commit, ParseError, SubscribeReposEvent, SubscribeReposSync};
+    use crate::validator::resync::write_car;
+    use crate::validator::types::{BlockMap, MAX_TREE_DEPTH, Node, NodeData};
+    use crate::validator::utils::ValidationError;
+
+    const DID: &str = "did:plc:ar7c4by46qjdydhdevvrndac";
+    const MAX_CLOCK_SKEW: Duration = Duration::from_secs(5 * 60);
+
+    fn block<T: Serialize>(value: &T) -> (Cid, Vec<u8>) {
+        #[expect(clippy::unwrap_used)]
+        let bytes = serde_ipld_dagcbor::to_vec(value).unwrap();
+        let cid = Cid::new_v1(<DagCborCodec as Codec<()>>::CODE, Code::Sha2_256.digest(&bytes));
+        (cid, bytes)
+    }
+
+    fn sync(root: &Cid, blocks: &[(Cid, &[u8])], rev: &TID) -> SubscribeReposEvent {
+        #[expect(clippy::unwrap_used)]
+        let blocks = write_car(root, blocks).unwrap();
+        SubscribeReposEvent::Sync(SubscribeReposSync {
+            seq: 1,
+            did: DID.to_owned(),
+            blocks,
+            rev: rev.clone(),
+            time: Utc::now(),
+        })
+    }
+
+    #[test]
+    fn sync_blocks() {
+        let rev = TID("3lr4pmliavk2l".to_owned());
+        let (data, record) = block(&"record");
+        let commit = Commit {
+            did: DID.to_owned(),
+            rev: rev.clone(),
+            data,
+            prev: None,
+            version: 3,
+            sig: Vec::new(),
+        };
+        let (head, bytes) = block(&commit);
+
+        let event = sync(&head, &[(head, &bytes)], &rev);
+        assert_eq!(event.validate(&commit, &head, MAX_CLOCK_SKEW), Ok(()));
+
+        // blocks besides the commit
+        let event = sync(&head, &[(head, &bytes), (data, &record)], &rev);
+        assert_eq!(
+            event.validate(&commit, &head, MAX_CLOCK_SKEW),
+            Err(ValidationError::SyncBlocks)
+        );
+
+        // the commit, but rooted elsewhere
+        let event = sync(&data, &[(head, &bytes)], &rev);
+        assert_eq!(
+            event.validate(&commit, &head, MAX_CLOCK_SKEW),
+            Err(ValidationError::SyncBlocks)
+        );
+
+        // a single block, but not the commit
+        let event = sync(&data, &[(data, &record)], &rev);
+        assert_eq!(
+            event.validate(&commit, &head, MAX_CLOCK_SKEW),
+            Err(ValidationError::SyncBlocks)
+        );
+    }
+
+    // a chain of empty nodes, each the left child of the previous one
+    fn chain(nodes: usize) -> (Cid, BlockMap) {
+        let mut blocks = BlockMap::new();
+        let mut left = None;
+        for _ in 0..nodes {
+            let (cid, bytes) = block(&NodeData { left, entries: Vec::new() });
+            blocks.insert(cid, bytes);
+            left = Some(cid);
+        }
+        #[expect(clippy::unwrap_used)]
+        (left.unwrap(), blocks)
+    }
+
+    #[test]
+    fn tree_depth() {
+        let (root, blocks) = chain(MAX_TREE_DEPTH + 1);
+        assert!(matches!(Node::load(&blocks, root), Ok(Some(_))));
+        let (root, blocks) = chain(MAX_TREE_DEPTH + 2);
+        assert!(matches!(Node::load(&blocks, root), Err(ParseError::TooDeep)));
+    }
+}
@@ -1,10 +1,15 @@
 use std::collections::TryReserveError;
+use std::time::Duration;
 
+use chrono::{DateTime, Utc};
 #[cfg(not(feature = "labeler"))]
 use cid::Cid;
 use p256::ecdsa::signature::Verifier;
 use thiserror::Error;
 
+#[cfg(not(feature = "labeler"))]
+use rsky_common::tid::TID;
+
 #[cfg(feature = "labeler")]
 use crate::validator::event::SubscribeLabel;
 #[cfg(not(feature = "labeler"))]
@@ -15,19 +20,184 @@ use crate::validator::types::RepoState;
 const P256_DID_PREFIX: &[u8] = &[0x80, 0x24];
 const K256_DID_PREFIX: &[u8] = &[0xe7, 0x01];
 
-#[derive(Debug, Error)]
-pub enum VerificationError {
-    #[error("serde error: {0}")]
-    Serde(#[from] serde_ipld_dagcbor::EncodeError<TryReserveError>),
-    #[error("key error: {0}")]
-    Key(#[from] p256::ecdsa::Error),
+/// Reason an event was rejected by the validator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Error)]
+pub enum ValidationError {
+    #[error("parse error")]
+    Parse,
+    #[error("commit decode error")]
+    CommitDecode,
+    #[cfg(not(feature = "labeler"))]
+    #[error("hostname pds mismatch")]
+    HostMismatch,
+    #[cfg(not(feature = "labeler"))]
+    #[error("host account limit reached")]
+    AccountLimit,
+    // event
+    #[cfg(not(feature = "labeler"))]
+    #[error("deprecated tooBig commit flag set")]
+    TooBig,
+    #[cfg(not(feature = "labeler"))]
+    #[error("deprecated rebase commit flag set")]
+    Rebase,
+    #[cfg(not(feature = "labeler"))]
+    #[error("mismatched inner commit cid")]
+    CommitCidMismatch,
+    #[cfg(not(feature = "labeler"))]
+    #[error("too many ops in commit")]
+    TooManyOps,
+    #[cfg(not(feature = "labeler"))]
+    #[error("commit messaging missing blocks")]
+    MissingBlocks,
+    #[cfg(not(feature = "labeler"))]
+    #[error("blocks size exceeds protocol limit")]
+    BlocksTooBig,
+    #[cfg(not(feature = "labeler"))]
+    #[error("mismatched inner commit did")]
+    DidMismatch,
+    #[cfg(not(feature = "labeler"))]
+    #[error("mismatched inner commit rev")]
+    RevMismatch,
+    #[cfg(not(feature = "labeler"))]
+    #[error("unsupported repo version")]
+    UnsupportedVersion,
+    #[cfg(not(feature = "labeler"))]
+    #[error("sync blocks hold more than the commit")]
+    SyncBlocks,
+    // signature
+    #[error("signature encode error")]
+    SignatureEncode,
+    #[error("malformed key or signature")]
+    SignatureMalformed,
+    #[error("signature mismatch")]
+    SignatureMismatch,
+    // commit
+    #[cfg(not(feature = "labeler"))]
+    #[error("old rev")]
+    OldRev,
+    #[cfg(not(feature = "labeler"))]
+    #[error("rev in the future")]
+    FutureRev,
+    #[cfg(not(feature = "labeler"))]
+    #[error("malformed rev")]
+    MalformedRev,
+    #[cfg(not(feature = "labeler"))]
+    #[error("missing since")]
+    MissingSince,
+    #[cfg(not(feature = "labeler"))]
+    #[error("miss-matching since")]
+    SinceMismatch,
+    #[cfg(not(feature = "labeler"))]
+    #[error("missing prevData")]
+    MissingPrevData,
+    #[cfg(not(feature = "labeler"))]
+    #[error("miss-matching prevData")]
+    PrevDataMismatch,
+    #[cfg(not(feature = "labeler"))]
+    #[error("commit doesn't chain from the repo state")]
+    ChainBreak,
+    #[cfg(not(feature = "labeler"))]
+    #[error("unable to read MST")]
+    UnreadableTree,
+    #[cfg(not(feature = "labeler"))]
+    #[error("unable to invert legacy op")]
+    LegacyOp,
+    #[cfg(not(feature = "labeler"))]
+    #[error("unable to invert op")]
+    InvertFailed,
+    #[cfg(not(feature = "labeler"))]
+    #[error("error while inverting op")]
+    InvertError,
+    #[cfg(not(feature = "labeler"))]
+    #[error("error while computing old root")]
+    RootError,
+    #[cfg(not(feature = "labeler"))]
+    #[error("inverted tree root didn't match prevData")]
+    RootMismatch,
+}
+
+impl ValidationError {
+    /// Stable identifier used when persisting rejection counters.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Parse => "parse",
+            Self::CommitDecode => "commit_decode",
+            #[cfg(not(feature = "labeler"))]
+            Self::HostMismatch => "host_mismatch",
+            #[cfg(not(feature = "labeler"))]
+            Self::AccountLimit => "account_limit",
+            #[cfg(not(feature = "labeler"))]
+            Self::TooBig => "too_big",
+            #[cfg(not(feature = "labeler"))]
+            Self::Rebase => "rebase",
+            #[cfg(not(feature = "labeler"))]
+            Self::CommitCidMismatch => "commit_cid_mismatch",
+            #[cfg(not(feature = "labeler"))]
+            Self::TooManyOps => "too_many_ops",
+            #[cfg(not(feature = "labeler"))]
+            Self::MissingBlocks => "missing_blocks",
+            #[cfg(not(feature = "labeler"))]
+            Self::BlocksTooBig => "blocks_too_big",
+            #[cfg(not(feature = "labeler"))]
+            Self::DidMismatch => "did_mismatch",
+            #[cfg(not(feature = "labeler"))]
+            Self::RevMismatch => "rev_mismatch",
+            #[cfg(not(feature = "labeler"))]
+            Self::UnsupportedVersion => "unsupported_version",
+            #[cfg(not(feature = "labeler"))]
+            Self::SyncBlocks => "sync_blocks",
+            Self::SignatureEncode => "signature_encode",
+            Self::SignatureMalformed => "signature_malformed",
+            Self::SignatureMismatch => "signature_mismatch",
+            #[cfg(not(feature = "labeler"))]
+            Self::OldRev => "old_rev",
+            #[cfg(not(feature = "labeler"))]
+            Self::FutureRev => "future_rev",
+            #[cfg(not(feature = "labeler"))]
+            Self::MalformedRev => "malformed_rev",
+            #[cfg(not(feature = "labeler"))]
+            Self::MissingSince => "missing_since",
+            #[cfg(not(feature = "labeler"))]
+            Self::SinceMismatch => "since_mismatch",
+            #[cfg(not(feature = "labeler"))]
+            Self::MissingPrevData => "missing_prev_data",
+            #[cfg(not(feature = "labeler"))]
+            Self::PrevDataMismatch => "prev_data_mismatch",
+            #[cfg(not(feature = "labeler"))]
+            Self::ChainBreak => "chain_break",
+            #[cfg(not(feature = "labeler"))]
+            Self::UnreadableTree => "unreadable_tree",
+            #[cfg(not(feature = "labeler"))]
+            Self::LegacyOp => "legacy_op",
+            #[cfg(not(feature = "labeler"))]
+            Self::InvertFailed => "invert_failed",
+            #[cfg(not(feature = "labeler"))]
+            Self::InvertError => "invert_error",
+            #[cfg(not(feature = "labeler"))]
+            Self::RootError => "root_error",
+            #[cfg(not(feature = "labeler"))]
+            Self::RootMismatch => "root_mismatch",
+        }
+    }
+}
+
+impl From<serde_ipld_dagcbor::EncodeError<TryReserveError>> for ValidationError {
+    fn from(err: serde_ipld_dagcbor::EncodeError<TryReserveError>) -> Self {
+        tracing::debug!(%err, "signature encode error");
+        Self::SignatureEncode
+    }
+}
+
+impl From<p256::ecdsa::Error> for ValidationError {
+    fn from(err: p256::ecdsa::Error) -> Self {
+        tracing::debug!(%err, "malformed key or signature");
+        Self::SignatureMalformed
+    }
 }
 
 #[cfg(feature = "labeler")]
-pub fn verify_commit_sig(
-    labels: &[SubscribeLabel], key: &[u8; 35],
-) -> Result<bool, VerificationError> {
-    let mut ret = true;
+pub fn verify_commit_sig(labels: &[SubscribeLabel], key: &[u8; 35]) -> Result<(), ValidationError> {
+    let mut valid = true;
     for label in labels {
         if let Some(sig) = &label.sig {
             let mut label = label.clone();
@@ -37,12 +207,12 @@ pub fn verify_commit_sig(
                 P256_DID_PREFIX => {
                     let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&key[2..])?;
                     let sig = p256::ecdsa::Signature::from_slice(sig)?;
-                    ret &= key.verify(&encoded, &sig).is_ok();
+                    valid &= key.verify(&encoded, &sig).is_ok();
                 }
                 K256_DID_PREFIX => {
                     let key = k256::ecdsa::VerifyingKey::from_sec1_bytes(&key[2..])?;
                     let sig = k256::ecdsa::Signature::from_slice(sig)?;
-                    ret &= key.verify(&encoded, &sig).is_ok();
+                    valid &= key.verify(&encoded, &sig).is_ok();
                 }
                 _ => {
                     unreachable!()
@@ -50,54 +220,71 @@ pub fn verify_commit_sig(
             }
         }
     }
-    Ok(ret)
+    if valid { Ok(()) } else { Err(ValidationError::SignatureMismatch) }
 }
 
 #[cfg(not(feature = "labeler"))]
-pub fn verify_commit_sig(commit: &Commit, key: &[u8; 35]) -> Result<bool, VerificationError> {
+pub fn verify_commit_sig(commit: &Commit, key: &[u8; 35]) -> Result<(), ValidationError> {
     let encoded = serde_ipld_dagcbor::to_vec(commit)?;
-    match &key[0..2] {
+    let valid = match &key[0..2] {
         P256_DID_PREFIX => {
             let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&key[2..])?;
             let sig = p256::ecdsa::Signature::from_slice(&commit.sig)?;
-            Ok(key.verify(&encoded, &sig).is_ok())
+            key.verify(&encoded, &sig).is_ok()
         }
         K256_DID_PREFIX => {
             let key = k256::ecdsa::VerifyingKey::from_sec1_bytes(&key[2..])?;
             let sig = k256::ecdsa::Signature::from_slice(&commit.sig)?;
-            Ok(key.verify(&encoded, &sig).is_ok())
+            key.verify(&encoded, &sig).is_ok()
         }
         _ => {
             unreachable!()
         }
-    }
+    };
+    if valid { Ok(()) } else { Err(ValidationError::SignatureMismatch) }
 }
 
+/// Verifies a `#commit` against the previous repo state by inverting its ops on the MST.
+///
+/// In lenient mode, only a stale rev and a missing `prevData` are rejected; every other failure
+/// is logged and the commit is accepted, so hosts still sending legacy ops keep working.
 #[cfg(not(feature = "labeler"))]
-pub fn verify_commit_event(commit: &SubscribeReposCommit, root: Cid, prev: &RepoState) -> bool {
+pub fn verify_commit_event(
+    commit: &SubscribeReposCommit, root: Cid, prev: &RepoState, strict: bool,
+) -> Result<(), ValidationError> {
+    // returns the error in strict mode, otherwise logs it and accepts the commit
+    let soft = |err: ValidationError| {
+        if strict {
+            Err(err)
+        } else {
+            tracing::trace!(%err, "ignoring in lenient mode");
+            Ok(())
+        }
+    };
+
     if !prev.rev.older_than(&commit.rev) {
-        tracing::debug!(diff = %commit.rev.timestamp() - prev.rev.timestamp(), "old rev");
-        return false;
+        tracing::debug!(prev = %prev.rev, rev = %commit.rev, "old rev");
+        return Err(ValidationError::OldRev);
     }
 
     if let Some(since) = &commit.since {
         if since != &prev.rev {
-            // TODO: change back to debug
             tracing::trace!(%since, "commit with miss-matching since");
+            soft(ValidationError::SinceMismatch)?;
         }
     } else {
         // NOTE: some PDSs don't send this field, so we continue verifying
         tracing::trace!("missing since");
+        soft(ValidationError::MissingSince)?;
     }
 
-    if let Some(prev_data) = &commit.prev_data {
-        if prev_data != &prev.data {
-            // TODO: change back to debug
-            tracing::trace!(%prev_data, "commit with miss-matching prevData");
-        }
-    } else {
+    let Some(prev_data) = commit.prev_data else {
         tracing::trace!("missing prev_data");
-        return false;
+        return Err(ValidationError::MissingPrevData);
+    };
+    if prev_data != prev.data {
+        tracing::trace!(%prev_data, "commit with miss-matching prevData");
+        soft(ValidationError::PrevDataMismatch)?;
     }
 
     let mut tree = match commit.tree(root) {
@@ -105,10 +292,10 @@ pub fn verify_commit_event(commit: &SubscribeReposCommit, root: Cid, prev: &Repo
         Err(err) => {
             if commit.ops.is_empty() && prev.data == root {
                 tracing::debug!(%err, "empty #commit");
-            } else {
-                tracing::debug!(%err, ops = %commit.ops.len(), "unable to read MST");
+                return Ok(());
             }
-            return true;
+            tracing::debug!(%err, ops = %commit.ops.len(), "unable to read MST");
+            return soft(ValidationError::UnreadableTree);
         }
     };
 
@@ -118,7 +305,7 @@ pub fn verify_commit_event(commit: &SubscribeReposCommit, root: Cid, prev: &Repo
     for op in &commit.ops {
         if !op.is_valid() {
             tracing::trace!(?op, "unable to invert legacy op");
-            return true;
+            return soft(ValidationError::LegacyOp);
         }
     }
 
@@ -128,12 +315,12 @@ pub fn verify_commit_event(commit: &SubscribeReposCommit, root: Cid, prev: &Repo
             Ok(inv) => {
                 if !inv {
                     tracing::debug!(%idx, ?op, "unable to invert op");
-                    return true;
+                    return soft(ValidationError::InvertFailed);
                 }
             }
             Err(err) => {
                 tracing::debug!(%idx, ?op, %err, "error while inverting op");
-                return true;
+                return soft(ValidationError::InvertError);
             }
         };
     }
@@ -142,17 +329,84 @@ pub fn verify_commit_event(commit: &SubscribeReposCommit, root: Cid, prev: &Repo
         Ok(computed) => computed,
         Err(err) => {
             tracing::debug!(%err, "error while computing old root");
-            return true;
+            return soft(ValidationError::RootError);
         }
     };
-    if let Some(prev_data) = commit.prev_data {
-        if prev_data != root {
-            tracing::debug!(%root, "inverted tree root didn't match prevData");
-            return true;
-        }
+    if prev_data != root {
+        tracing::debug!(%root, "inverted tree root didn't match prevData");
+        return soft(ValidationError::RootMismatch);
+    }
+
+    Ok(())
+}
+
+/// Checks a `#sync` against the known repo state, which it then replaces.
+///
+/// Only the rev can be checked, as a sync may follow any number of missed commits.
+#[cfg(not(feature = "labeler"))]
+pub fn verify_sync_event(rev: &TID, prev: &RepoState) -> Result<(), ValidationError> {
+    if !prev.rev.older_than(rev) {
+        tracing::debug!(prev = %prev.rev, %rev, "old rev");
+        return Err(ValidationError::OldRev);
+    }
+    Ok(())
+}
+
+/// Whether a newer `#commit` doesn't chain from the known repo state, ie the relay missed commits.
+///
+/// Missing `since`/`prevData` fields are left to `verify_commit_event`.
+#[cfg(not(feature = "labeler"))]
+pub fn breaks_chain(commit: &SubscribeReposCommit, prev: &RepoState) -> bool {
+    prev.rev.older_than(&commit.rev)
+        && (commit.since.as_ref().is_some_and(|since| since != &prev.rev)
+            || commit.prev_data.is_some_and(|prev_data| prev_data != prev.data))
+}
+
+// microseconds since the epoch past which a timestamp is too far ahead of our clock
+fn horizon(now: DateTime<Utc>, max_clock_skew: Duration) -> i64 {
+    let skew = i64::try_from(max_clock_skew.as_micros()).unwrap_or(i64::MAX);
+    now.timestamp_micros().saturating_add(skew)
+}
+
+/// Caps an event `time` to `max_clock_skew` ahead of the local clock, as it would otherwise become
+/// the host's latest time. The event itself is still relayed.
+pub fn clamp_time(time: DateTime<Utc>, max_clock_skew: Duration) -> DateTime<Utc> {
+    clamp_time_at(time, max_clock_skew, Utc::now())
+}
+
+fn clamp_time_at(
+    time: DateTime<Utc>, max_clock_skew: Duration, now: DateTime<Utc>,
+) -> DateTime<Utc> {
+    let horizon = horizon(now, max_clock_skew);
+    if time.timestamp_micros() <= horizon {
+        return time;
     }
+    tracing::debug!(%time, "event time in the future");
+    DateTime::from_timestamp_micros(horizon).unwrap_or(time)
+}
 
-    true
+/// Rejects a rev further ahead of the local clock than `max_clock_skew`, as it would make every
+/// later commit of the repo fail as an old rev.
+#[cfg(not(feature = "labeler"))]
+pub fn verify_rev(rev: &TID, max_clock_skew: Duration) -> Result<(), ValidationError> {
+    verify_rev_at(rev, max_clock_skew, Utc::now())
+}
+
+#[cfg(not(feature = "labeler"))]
+fn verify_rev_at(
+    rev: &TID, max_clock_skew: Duration, now: DateTime<Utc>,
+) -> Result<(), ValidationError> {
+    // `TID::timestamp` panics on anything but base32-sortable characters
+    if rev.0.len() != 13 || !rev.0.bytes().all(|b| matches!(b, b'2'..=b'7' | b'a'..=b'z')) {
+        tracing::debug!(%rev, "malformed rev");
+        return Err(ValidationError::MalformedRev);
+    }
+    let timestamp = i64::try_from(rev.timestamp()).unwrap_or(i64::MAX);
+    if timestamp > horizon(now, max_clock_skew) {
+        tracing::debug!(%rev, "rev in the future");
+        return Err(ValidationError::FutureRev);
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -169,6 +423,112 @@ mod tests {
         let Ok(Some(SubscribeReposEvent::Labels(labels))) = SubscribeReposEvent::parse(MSG) else {
             unreachable!()
         };
-        assert!(verify_commit_sig(&labels.labels, KEY).unwrap_or(false));
+        assert!(verify_commit_sig(&labels.labels, KEY).is_ok());
+    }
+
+    #[cfg(not(feature = "labeler"))]
+    #[test]
+    fn verify_commit_strict() {
+        use chrono::Utc;
+        use cid::Cid;
+        use rsky_common::tid::TID;
+
+        use crate::validator::event::SubscribeReposCommit;
+        use crate::validator::types::RepoState;
+        use crate::validator::utils::{ValidationError, verify_commit_event};
+
+        #[expect(clippy::unwrap_used)]
+        let data =
+            Cid::try_from("bafyreiapddjgxnyaogx2gvakuawukls5rr2hdwbkrjb4nwjffwpkb4734m").unwrap();
+        let prev = RepoState { rev: TID("3lr4pmliavk2l".to_owned()), data, head: data };
+        // an empty commit chaining from `prev`, only missing its `since`
+        let commit = SubscribeReposCommit {
+            seq: 1,
+            rebase: false,
+            too_big: false,
+            did: "did:plc:ar7c4by46qjdydhdevvrndac".to_owned(),
+            commit: data,
+            rev: TID("3lr4pmliavk3l".to_owned()),
+            since: None,
+            blocks: Vec::new(),
+            ops: Vec::new(),
+            blobs: Vec::new(),
+            prev_data: Some(data),
+            time: Utc::now(),
+        };
+        assert_eq!(verify_commit_event(&commit, data, &prev, false), Ok(()));
+        assert_eq!(
+            verify_commit_event(&commit, data, &prev, true),
+            Err(ValidationError::MissingSince)
+        );
+    }
+
+    #[cfg(not(feature = "labeler"))]
+    #[test]
+    fn verify_sync() {
+        use cid::Cid;
+        use rsky_common::tid::TID;
+
+        use crate::validator::types::RepoState;
+        use crate::validator::utils::{ValidationError, verify_sync_event};
+
+        #[expect(clippy::unwrap_used)]
+        let data =
+            Cid::try_from("bafyreiapddjgxnyaogx2gvakuawukls5rr2hdwbkrjb4nwjffwpkb4734m").unwrap();
+        let prev = RepoState { rev: TID("3lr4pmliavk2l".to_owned()), data, head: data };
+        assert_eq!(verify_sync_event(&TID("3lr4pmliavk3l".to_owned()), &prev), Ok(()));
+        // replayed and older revs, logged without comparing their timestamps
+        assert_eq!(verify_sync_event(&prev.rev, &prev), Err(ValidationError::OldRev));
+        assert_eq!(
+            verify_sync_event(&TID("3lr4pmliavk22".to_owned()), &prev),
+            Err(ValidationError::OldRev)
+        );
+    }
+
+    #[cfg(not(feature = "labeler"))]
+    #[test]
+    fn verify_revs() {
+        use std::time::Duration;
+
+        use chrono::Utc;
+        use rsky_common::tid::TID;
+
+        use crate::validator::utils::{ValidationError, verify_rev_at};
+
+        const SKEW: Duration = Duration::from_secs(5 * 60);
+        let now = Utc::now();
+        let horizon = now.timestamp_micros() + 5 * 60 * 1_000_000;
+        let rev = |micros: i64| TID::from_time(usize::try_from(micros).unwrap_or_default(), 0);
+
+        assert_eq!(verify_rev_at(&rev(now.timestamp_micros()), SKEW, now), Ok(()));
+        assert_eq!(verify_rev_at(&rev(horizon), SKEW, now), Ok(()));
+        assert_eq!(verify_rev_at(&rev(horizon + 1), SKEW, now), Err(ValidationError::FutureRev));
+        // too short, too long, uppercase, and outside the base32-sortable alphabet
+        for malformed in ["", "3lr4pmliavk2", "3lr4pmliavk2l2", "3LR4PMLIAVK2L", "3lr4pmliavk1l"] {
+            assert_eq!(
+                verify_rev_at(&TID(malformed.to_owned()), SKEW, now),
+                Err(ValidationError::MalformedRev),
+                "{malformed}"
+            );
+        }
+    }
+
+    #[test]
+    fn clamp_times() {
+        use std::time::Duration;
+
+        use chrono::{SubsecRound, TimeDelta, Utc};
+
+        use crate::validator::utils::clamp_time_at;
+
+        const SKEW: Duration = Duration::from_secs(5 * 60);
+        // clamped times have microsecond precision
+        let now = Utc::now().trunc_subsecs(6);
+        let horizon = now + TimeDelta::minutes(5);
+        let past = now - TimeDelta::days(1);
+        assert_eq!(clamp_time_at(past, SKEW, now), past);
+        assert_eq!(clamp_time_at(horizon, SKEW, now), horizon);
+        assert_eq!(clamp_time_at(horizon + TimeDelta::microseconds(1), SKEW, now), horizon);
+        assert_eq!(clamp_time_at(now + TimeDelta::days(365), SKEW, now), horizon);
     }
 }